@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_bridge_messages_delivered_at;
+DROP INDEX IF EXISTS idx_bridge_messages_unmatched;
+
+DROP TRIGGER IF EXISTS set_updated_at ON bridge_messages;
+DROP TABLE bridge_messages;
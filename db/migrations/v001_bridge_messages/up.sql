@@ -0,0 +1,19 @@
+CREATE TABLE bridge_messages (
+    source_chain_id BIGINT NOT NULL,
+    destination_chain_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    sent_block BIGINT,
+    sent_at BIGINT,
+    delivered_block BIGINT,
+    delivered_at BIGINT,
+    latency_secs BIGINT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (source_chain_id, destination_chain_id, message_id)
+);
+
+SELECT diesel_manage_updated_at('bridge_messages');
+
+CREATE INDEX IF NOT EXISTS idx_bridge_messages_delivered_at ON bridge_messages (delivered_at);
+CREATE INDEX IF NOT EXISTS idx_bridge_messages_unmatched ON bridge_messages (latency_secs) WHERE latency_secs IS NULL;
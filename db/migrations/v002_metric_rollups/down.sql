@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_metric_rollups_bucket;
+
+DROP TRIGGER IF EXISTS set_updated_at ON metric_rollups;
+DROP TABLE metric_rollups;
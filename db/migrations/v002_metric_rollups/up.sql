@@ -0,0 +1,19 @@
+CREATE TABLE metric_rollups (
+    chain_id BIGINT NOT NULL,
+    bucket_secs BIGINT NOT NULL,
+    bucket_start BIGINT NOT NULL,
+    tx_type TEXT NOT NULL,
+    tx_count BIGINT NOT NULL DEFAULT 0,
+    successful BIGINT NOT NULL DEFAULT 0,
+    failed BIGINT NOT NULL DEFAULT 0,
+    gas_used BIGINT NOT NULL DEFAULT 0,
+    value NUMERIC(78, 0) NOT NULL DEFAULT 0,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id, bucket_secs, tx_type, bucket_start)
+);
+
+SELECT diesel_manage_updated_at('metric_rollups');
+
+CREATE INDEX IF NOT EXISTS idx_metric_rollups_bucket ON metric_rollups (bucket_secs, tx_type, bucket_start);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_blocks_timestamp;
+
+DROP TRIGGER IF EXISTS set_updated_at ON blocks;
+DROP TABLE blocks;
@@ -0,0 +1,17 @@
+CREATE TABLE blocks (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    block_timestamp BIGINT NOT NULL,
+    tx_count BIGINT NOT NULL,
+    counts JSONB NOT NULL,
+    bridged JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id, block_number)
+);
+
+SELECT diesel_manage_updated_at('blocks');
+
+CREATE INDEX IF NOT EXISTS idx_blocks_timestamp ON blocks (block_timestamp);
@@ -0,0 +1,28 @@
+CREATE OR REPLACE FUNCTION numeric_to_hex(value NUMERIC) RETURNS TEXT AS $$
+DECLARE
+    digits TEXT := '';
+BEGIN
+    WHILE value > 0 LOOP
+        digits := substr('0123456789abcdef', (value % 16)::INT + 1, 1) || digits;
+        value := div(value, 16);
+    END LOOP;
+    RETURN '0x' || lpad(digits, 64, '0');
+END;
+$$ LANGUAGE plpgsql IMMUTABLE STRICT;
+
+DROP INDEX IF EXISTS idx_transactions_value;
+
+ALTER TABLE transactions
+    ALTER COLUMN transaction_hash TYPE TEXT USING '0x' || encode(transaction_hash, 'hex'),
+    ALTER COLUMN transaction_nonce TYPE TEXT USING numeric_to_hex(transaction_nonce),
+    ALTER COLUMN block_hash TYPE TEXT USING '0x' || encode(block_hash, 'hex'),
+    ALTER COLUMN _from TYPE TEXT USING '0x' || encode(_from, 'hex'),
+    ALTER COLUMN _to TYPE TEXT USING '0x' || encode(_to, 'hex'),
+    ALTER COLUMN value TYPE TEXT USING numeric_to_hex(value),
+    ALTER COLUMN gas_price TYPE TEXT USING numeric_to_hex(gas_price),
+    ALTER COLUMN gas TYPE TEXT USING numeric_to_hex(gas),
+    ALTER COLUMN input TYPE TEXT USING '0x' || encode(input, 'hex'),
+    ALTER COLUMN max_priority_fee_per_gas TYPE TEXT USING numeric_to_hex(max_priority_fee_per_gas),
+    ALTER COLUMN max_fee_per_gas TYPE TEXT USING numeric_to_hex(max_fee_per_gas);
+
+DROP FUNCTION numeric_to_hex(NUMERIC);
@@ -0,0 +1,35 @@
+-- Amounts, gas and nonces were stored as 32-byte zero-padded hex, and hashes,
+-- addresses and input as hex text.
+CREATE OR REPLACE FUNCTION hex_to_numeric(hex TEXT) RETURNS NUMERIC AS $$
+DECLARE
+    digits TEXT := lower(regexp_replace(hex, '^0x', ''));
+    result NUMERIC := 0;
+BEGIN
+    FOR i IN 1..length(digits) LOOP
+        result := result * 16 + (position(substr(digits, i, 1) IN '0123456789abcdef') - 1);
+    END LOOP;
+    RETURN result;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE STRICT;
+
+CREATE OR REPLACE FUNCTION hex_to_bytea(hex TEXT) RETURNS BYTEA AS $$
+    SELECT decode(regexp_replace(hex, '^0x', ''), 'hex');
+$$ LANGUAGE sql IMMUTABLE STRICT;
+
+ALTER TABLE transactions
+    ALTER COLUMN transaction_hash TYPE BYTEA USING hex_to_bytea(transaction_hash),
+    ALTER COLUMN transaction_nonce TYPE BIGINT USING hex_to_numeric(transaction_nonce)::BIGINT,
+    ALTER COLUMN block_hash TYPE BYTEA USING hex_to_bytea(block_hash),
+    ALTER COLUMN _from TYPE BYTEA USING hex_to_bytea(_from),
+    ALTER COLUMN _to TYPE BYTEA USING hex_to_bytea(_to),
+    ALTER COLUMN value TYPE NUMERIC(78, 0) USING hex_to_numeric(value),
+    ALTER COLUMN gas_price TYPE NUMERIC(78, 0) USING hex_to_numeric(gas_price),
+    ALTER COLUMN gas TYPE BIGINT USING hex_to_numeric(gas)::BIGINT,
+    ALTER COLUMN input TYPE BYTEA USING hex_to_bytea(input),
+    ALTER COLUMN max_priority_fee_per_gas TYPE NUMERIC(78, 0) USING hex_to_numeric(max_priority_fee_per_gas),
+    ALTER COLUMN max_fee_per_gas TYPE NUMERIC(78, 0) USING hex_to_numeric(max_fee_per_gas);
+
+DROP FUNCTION hex_to_numeric(TEXT);
+DROP FUNCTION hex_to_bytea(TEXT);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_value ON transactions (value);
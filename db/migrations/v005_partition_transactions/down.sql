@@ -0,0 +1,54 @@
+ALTER TABLE transactions RENAME TO transactions_partitioned;
+ALTER TABLE transactions_partitioned RENAME CONSTRAINT transactions_pkey TO transactions_partitioned_pkey;
+
+CREATE TABLE transactions (
+    chain_id BIGINT NOT NULL REFERENCES chains(chain_id),
+    transaction_hash BYTEA NOT NULL,
+    transaction_nonce BIGINT NOT NULL,
+    block_hash BYTEA,
+    block_number BIGINT,
+    transaction_index BIGINT,
+    _from BYTEA NOT NULL,
+    _to BYTEA,
+    value NUMERIC(78, 0) NOT NULL,
+    gas_price NUMERIC(78, 0),
+    gas BIGINT NOT NULL,
+    input BYTEA NOT NULL,
+    v TEXT NOT NULL,
+    r TEXT NOT NULL,
+    s TEXT NOT NULL,
+    transaction_type TEXT NOT NULL,
+    impersonated BOOLEAN NOT NULL,
+    max_priority_fee_per_gas NUMERIC(78, 0),
+    max_fee_per_gas NUMERIC(78, 0),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    tx_type TEXT,
+
+    PRIMARY KEY (chain_id, transaction_hash)
+);
+
+-- A transaction re-indexed at another block after a reorg is kept once
+INSERT INTO transactions
+SELECT DISTINCT ON (chain_id, transaction_hash)
+    chain_id, transaction_hash, transaction_nonce, block_hash, block_number, transaction_index,
+    _from, _to, value, gas_price, gas, input, v, r, s, transaction_type, impersonated,
+    max_priority_fee_per_gas, max_fee_per_gas, created_at, updated_at, tx_type
+FROM transactions_partitioned
+ORDER BY chain_id, transaction_hash, block_number DESC;
+
+DROP TABLE transactions_partitioned;
+DROP FUNCTION ensure_transactions_partition(BIGINT, BIGINT, BIGINT);
+
+SELECT diesel_manage_updated_at('transactions');
+
+CREATE INDEX IF NOT EXISTS idx_transactions_hash ON transactions (transaction_hash);
+CREATE INDEX IF NOT EXISTS idx_transactions_block_hash ON transactions (block_hash);
+CREATE INDEX IF NOT EXISTS idx_transactions_block_number ON transactions (block_number);
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_id ON transactions (chain_id);
+CREATE INDEX IF NOT EXISTS idx_transactions_index ON transactions (chain_id, block_number, transaction_index);
+CREATE INDEX IF NOT EXISTS idx_transactions_from ON transactions (_from);
+CREATE INDEX IF NOT EXISTS idx_transactions_to ON transactions (_to);
+CREATE INDEX IF NOT EXISTS idx_transactions_type ON transactions (transaction_type);
+CREATE INDEX IF NOT EXISTS idx_transactions_impersonated ON transactions (impersonated);
+CREATE INDEX IF NOT EXISTS idx_transactions_value ON transactions (value);
@@ -0,0 +1,106 @@
+-- Transactions are partitioned by chain, and each chain by ranges of blocks.
+-- The unique key of a partitioned table must include every partition key, so
+-- block_number becomes part of the primary key and can no longer be NULL.
+ALTER TABLE transactions RENAME TO transactions_unpartitioned;
+ALTER TABLE transactions_unpartitioned RENAME CONSTRAINT transactions_pkey TO transactions_unpartitioned_pkey;
+DROP TRIGGER IF EXISTS set_updated_at ON transactions_unpartitioned;
+DROP INDEX IF EXISTS idx_transactions_hash;
+DROP INDEX IF EXISTS idx_transactions_block_hash;
+DROP INDEX IF EXISTS idx_transactions_block_number;
+DROP INDEX IF EXISTS idx_transactions_chain_id;
+DROP INDEX IF EXISTS idx_transactions_index;
+DROP INDEX IF EXISTS idx_transactions_from;
+DROP INDEX IF EXISTS idx_transactions_to;
+DROP INDEX IF EXISTS idx_transactions_type;
+DROP INDEX IF EXISTS idx_transactions_impersonated;
+DROP INDEX IF EXISTS idx_transactions_value;
+
+CREATE TABLE transactions (
+    chain_id BIGINT NOT NULL REFERENCES chains(chain_id),
+    transaction_hash BYTEA NOT NULL,
+    transaction_nonce BIGINT NOT NULL,
+    block_hash BYTEA,
+    block_number BIGINT NOT NULL,
+    transaction_index BIGINT,
+    _from BYTEA NOT NULL,
+    _to BYTEA,
+    value NUMERIC(78, 0) NOT NULL,
+    gas_price NUMERIC(78, 0),
+    gas BIGINT NOT NULL,
+    input BYTEA NOT NULL,
+    v TEXT NOT NULL,
+    r TEXT NOT NULL,
+    s TEXT NOT NULL,
+    transaction_type TEXT NOT NULL,
+    impersonated BOOLEAN NOT NULL,
+    max_priority_fee_per_gas NUMERIC(78, 0),
+    max_fee_per_gas NUMERIC(78, 0),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    tx_type TEXT,
+
+    PRIMARY KEY (chain_id, block_number, transaction_hash)
+) PARTITION BY LIST (chain_id);
+
+SELECT diesel_manage_updated_at('transactions');
+
+-- Pages of a chain, optionally of one type, latest block first
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_block ON transactions (chain_id, block_number DESC, transaction_index DESC);
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_type_block ON transactions (chain_id, tx_type, block_number DESC);
+-- Lookups by hash, which may not name the chain
+CREATE INDEX IF NOT EXISTS idx_transactions_hash ON transactions (transaction_hash);
+-- Activity of an account on a chain
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_from_block ON transactions (chain_id, _from, block_number DESC);
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_to_block ON transactions (chain_id, _to, block_number DESC);
+
+-- Creates the partition of a chain and the partition of its blocks
+-- [first_block, first_block + blocks) if they do not exist. Called by the
+-- indexer before inserting into a new range.
+CREATE OR REPLACE FUNCTION ensure_transactions_partition(chain BIGINT, first_block BIGINT, blocks BIGINT) RETURNS VOID AS $$
+DECLARE
+    chain_partition TEXT := format('transactions_%s', chain);
+    range_partition TEXT := format('transactions_%s_%s', chain, first_block);
+BEGIN
+    IF to_regclass(chain_partition) IS NULL THEN
+        BEGIN
+            EXECUTE format(
+                'CREATE TABLE %I PARTITION OF transactions FOR VALUES IN (%s) PARTITION BY RANGE (block_number)',
+                chain_partition, chain
+            );
+        EXCEPTION WHEN duplicate_table THEN
+            -- Created concurrently by another writer
+        END;
+    END IF;
+
+    IF to_regclass(range_partition) IS NULL THEN
+        BEGIN
+            EXECUTE format(
+                'CREATE TABLE %I PARTITION OF %I FOR VALUES FROM (%s) TO (%s)',
+                range_partition, chain_partition, first_block, first_block + blocks
+            );
+        EXCEPTION WHEN duplicate_table THEN
+        END;
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Same range width as TRANSACTION_PARTITION_BLOCKS. Transactions without a
+-- block were never indexed; they are kept at block 0.
+SELECT ensure_transactions_partition(chain_id, start_block, 1000000)
+FROM (
+    SELECT DISTINCT chain_id, COALESCE(block_number, 0) / 1000000 * 1000000 AS start_block
+    FROM transactions_unpartitioned
+) ranges;
+
+INSERT INTO transactions (
+    chain_id, transaction_hash, transaction_nonce, block_hash, block_number, transaction_index,
+    _from, _to, value, gas_price, gas, input, v, r, s, transaction_type, impersonated,
+    max_priority_fee_per_gas, max_fee_per_gas, created_at, updated_at, tx_type
+)
+SELECT
+    chain_id, transaction_hash, transaction_nonce, block_hash, COALESCE(block_number, 0), transaction_index,
+    _from, _to, value, gas_price, gas, input, v, r, s, transaction_type, impersonated,
+    max_priority_fee_per_gas, max_fee_per_gas, created_at, updated_at, tx_type
+FROM transactions_unpartitioned;
+
+DROP TABLE transactions_unpartitioned;
@@ -0,0 +1,15 @@
+-- Writes that still failed after every retry of the write queue, kept with
+-- their payload so they can be replayed.
+CREATE TABLE dead_letters (
+    id BIGSERIAL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    error TEXT NOT NULL,
+    attempts INT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+SELECT diesel_manage_updated_at('dead_letters');
+
+CREATE INDEX IF NOT EXISTS idx_dead_letters_kind ON dead_letters (kind, id);
@@ -0,0 +1,16 @@
+-- Blocks the indexer failed to process, with the last error. Skipped blocks
+-- were given up on after too many attempts and wait for a manual retry.
+CREATE TABLE failed_blocks (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    error TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 1,
+    skipped BOOLEAN NOT NULL DEFAULT FALSE,
+    failed_at BIGINT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id, block_number)
+);
+
+SELECT diesel_manage_updated_at('failed_blocks');
@@ -0,0 +1,7 @@
+ALTER TABLE transactions
+    DROP COLUMN access_list,
+    DROP COLUMN max_fee_per_blob_gas,
+    DROP COLUMN blob_versioned_hashes,
+    DROP COLUMN authorization_list,
+    DROP COLUMN y_parity,
+    DROP COLUMN raw_transaction;
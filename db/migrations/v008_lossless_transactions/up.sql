@@ -0,0 +1,10 @@
+-- Fields of EIP-2930, EIP-1559, EIP-4844 and EIP-7702 transactions, and the
+-- EIP-2718 envelope as broadcast. Rows indexed before this migration keep
+-- them NULL.
+ALTER TABLE transactions
+    ADD COLUMN access_list JSONB,
+    ADD COLUMN max_fee_per_blob_gas NUMERIC(78, 0),
+    ADD COLUMN blob_versioned_hashes JSONB,
+    ADD COLUMN authorization_list JSONB,
+    ADD COLUMN y_parity BOOLEAN,
+    ADD COLUMN raw_transaction BYTEA;
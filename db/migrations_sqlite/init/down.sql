@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS blocks;
+DROP TABLE IF EXISTS metric_rollups;
+DROP TABLE IF EXISTS bridge_messages;
+DROP TABLE IF EXISTS transactions;
+DROP TABLE IF EXISTS chains;
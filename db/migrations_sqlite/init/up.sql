@@ -0,0 +1,128 @@
+CREATE TABLE chains (
+    chain_id BIGINT NOT NULL,
+    latest_tps BIGINT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id)
+);
+
+CREATE TABLE transactions (
+    chain_id BIGINT NOT NULL REFERENCES chains(chain_id),
+    transaction_hash TEXT NOT NULL,
+    transaction_nonce TEXT NOT NULL,
+    block_hash TEXT,
+    block_number BIGINT,
+    transaction_index BIGINT,
+    _from TEXT NOT NULL,
+    _to TEXT,
+    value TEXT NOT NULL,
+    gas_price TEXT,
+    gas TEXT NOT NULL,
+    input TEXT NOT NULL,
+    v TEXT NOT NULL,
+    r TEXT NOT NULL,
+    s TEXT NOT NULL,
+    transaction_type TEXT NOT NULL,
+    impersonated BOOLEAN NOT NULL,
+    max_priority_fee_per_gas TEXT,
+    max_fee_per_gas TEXT,
+    tx_type TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id, transaction_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_hash ON transactions (transaction_hash);
+CREATE INDEX IF NOT EXISTS idx_transactions_block_hash ON transactions (block_hash);
+CREATE INDEX IF NOT EXISTS idx_transactions_block_number ON transactions (block_number);
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_id ON transactions (chain_id);
+CREATE INDEX IF NOT EXISTS idx_transactions_index ON transactions (chain_id, block_number, transaction_index);
+CREATE INDEX IF NOT EXISTS idx_transactions_from ON transactions (_from);
+CREATE INDEX IF NOT EXISTS idx_transactions_to ON transactions (_to);
+CREATE INDEX IF NOT EXISTS idx_transactions_type ON transactions (transaction_type);
+CREATE INDEX IF NOT EXISTS idx_transactions_impersonated ON transactions (impersonated);
+
+CREATE TABLE bridge_messages (
+    source_chain_id BIGINT NOT NULL,
+    destination_chain_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    sent_block BIGINT,
+    sent_at BIGINT,
+    delivered_block BIGINT,
+    delivered_at BIGINT,
+    latency_secs BIGINT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (source_chain_id, destination_chain_id, message_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_bridge_messages_delivered_at ON bridge_messages (delivered_at);
+CREATE INDEX IF NOT EXISTS idx_bridge_messages_unmatched ON bridge_messages (latency_secs) WHERE latency_secs IS NULL;
+
+-- `value` holds a decimal uint256, summed by the indexer
+CREATE TABLE metric_rollups (
+    chain_id BIGINT NOT NULL,
+    bucket_secs BIGINT NOT NULL,
+    bucket_start BIGINT NOT NULL,
+    tx_type TEXT NOT NULL,
+    tx_count BIGINT NOT NULL DEFAULT 0,
+    successful BIGINT NOT NULL DEFAULT 0,
+    failed BIGINT NOT NULL DEFAULT 0,
+    gas_used BIGINT NOT NULL DEFAULT 0,
+    value TEXT NOT NULL DEFAULT '0',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id, bucket_secs, tx_type, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_metric_rollups_bucket ON metric_rollups (bucket_secs, tx_type, bucket_start);
+
+CREATE TABLE blocks (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    block_timestamp BIGINT NOT NULL,
+    tx_count BIGINT NOT NULL,
+    counts TEXT NOT NULL,
+    bridged TEXT NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id, block_number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_blocks_timestamp ON blocks (block_timestamp);
+
+CREATE TRIGGER chains_set_updated_at AFTER UPDATE ON chains FOR EACH ROW
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE chains SET updated_at = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER transactions_set_updated_at AFTER UPDATE ON transactions FOR EACH ROW
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE transactions SET updated_at = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER bridge_messages_set_updated_at AFTER UPDATE ON bridge_messages FOR EACH ROW
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE bridge_messages SET updated_at = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER metric_rollups_set_updated_at AFTER UPDATE ON metric_rollups FOR EACH ROW
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE metric_rollups SET updated_at = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER blocks_set_updated_at AFTER UPDATE ON blocks FOR EACH ROW
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE blocks SET updated_at = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;
+END;
@@ -0,0 +1,47 @@
+-- SQLite has no 256-bit integers, so amounts are decimal text. The SQLite
+-- store only holds local data, so its transactions are re-indexed rather than
+-- converted.
+DROP TABLE transactions;
+
+CREATE TABLE transactions (
+    chain_id BIGINT NOT NULL REFERENCES chains(chain_id),
+    transaction_hash BLOB NOT NULL,
+    transaction_nonce BIGINT NOT NULL,
+    block_hash BLOB,
+    block_number BIGINT,
+    transaction_index BIGINT,
+    _from BLOB NOT NULL,
+    _to BLOB,
+    value TEXT NOT NULL,
+    gas_price TEXT,
+    gas BIGINT NOT NULL,
+    input BLOB NOT NULL,
+    v TEXT NOT NULL,
+    r TEXT NOT NULL,
+    s TEXT NOT NULL,
+    transaction_type TEXT NOT NULL,
+    impersonated BOOLEAN NOT NULL,
+    max_priority_fee_per_gas TEXT,
+    max_fee_per_gas TEXT,
+    tx_type TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id, transaction_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_hash ON transactions (transaction_hash);
+CREATE INDEX IF NOT EXISTS idx_transactions_block_hash ON transactions (block_hash);
+CREATE INDEX IF NOT EXISTS idx_transactions_block_number ON transactions (block_number);
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_id ON transactions (chain_id);
+CREATE INDEX IF NOT EXISTS idx_transactions_index ON transactions (chain_id, block_number, transaction_index);
+CREATE INDEX IF NOT EXISTS idx_transactions_from ON transactions (_from);
+CREATE INDEX IF NOT EXISTS idx_transactions_to ON transactions (_to);
+CREATE INDEX IF NOT EXISTS idx_transactions_type ON transactions (transaction_type);
+CREATE INDEX IF NOT EXISTS idx_transactions_impersonated ON transactions (impersonated);
+
+CREATE TRIGGER transactions_set_updated_at AFTER UPDATE ON transactions FOR EACH ROW
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE transactions SET updated_at = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;
+END;
@@ -0,0 +1,14 @@
+-- SQLite has no table partitioning; only the indexes follow the Postgres ones.
+DROP INDEX IF EXISTS idx_transactions_block_hash;
+DROP INDEX IF EXISTS idx_transactions_block_number;
+DROP INDEX IF EXISTS idx_transactions_chain_id;
+DROP INDEX IF EXISTS idx_transactions_index;
+DROP INDEX IF EXISTS idx_transactions_from;
+DROP INDEX IF EXISTS idx_transactions_to;
+DROP INDEX IF EXISTS idx_transactions_type;
+DROP INDEX IF EXISTS idx_transactions_impersonated;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_block ON transactions (chain_id, block_number DESC, transaction_index DESC);
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_type_block ON transactions (chain_id, tx_type, block_number DESC);
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_from_block ON transactions (chain_id, _from, block_number DESC);
+CREATE INDEX IF NOT EXISTS idx_transactions_chain_to_block ON transactions (chain_id, _to, block_number DESC);
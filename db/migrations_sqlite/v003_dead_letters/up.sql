@@ -0,0 +1,17 @@
+CREATE TABLE dead_letters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_dead_letters_kind ON dead_letters (kind, id);
+
+CREATE TRIGGER dead_letters_set_updated_at AFTER UPDATE ON dead_letters FOR EACH ROW
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE dead_letters SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
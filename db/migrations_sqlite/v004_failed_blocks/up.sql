@@ -0,0 +1,18 @@
+CREATE TABLE failed_blocks (
+    chain_id BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    skipped BOOLEAN NOT NULL DEFAULT FALSE,
+    failed_at BIGINT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (chain_id, block_number)
+);
+
+CREATE TRIGGER failed_blocks_set_updated_at AFTER UPDATE ON failed_blocks FOR EACH ROW
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE failed_blocks SET updated_at = CURRENT_TIMESTAMP WHERE rowid = NEW.rowid;
+END;
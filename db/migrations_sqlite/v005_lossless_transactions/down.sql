@@ -0,0 +1,6 @@
+ALTER TABLE transactions DROP COLUMN access_list;
+ALTER TABLE transactions DROP COLUMN max_fee_per_blob_gas;
+ALTER TABLE transactions DROP COLUMN blob_versioned_hashes;
+ALTER TABLE transactions DROP COLUMN authorization_list;
+ALTER TABLE transactions DROP COLUMN y_parity;
+ALTER TABLE transactions DROP COLUMN raw_transaction;
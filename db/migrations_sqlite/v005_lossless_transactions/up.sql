@@ -0,0 +1,6 @@
+ALTER TABLE transactions ADD COLUMN access_list TEXT;
+ALTER TABLE transactions ADD COLUMN max_fee_per_blob_gas TEXT;
+ALTER TABLE transactions ADD COLUMN blob_versioned_hashes TEXT;
+ALTER TABLE transactions ADD COLUMN authorization_list TEXT;
+ALTER TABLE transactions ADD COLUMN y_parity BOOLEAN;
+ALTER TABLE transactions ADD COLUMN raw_transaction BLOB;
@@ -0,0 +1,159 @@
+use alloy::{
+    primitives::{keccak256, Address, FixedBytes, Log as PrimitiveLog, U256},
+    rpc::types::{Log, TransactionReceipt},
+    sol_types::SolEvent,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::ETHReceivedFromSourceChainInBatch;
+
+/// Location of a value inside an emitted event, either an indexed topic or a
+/// 32-byte word of the non-indexed data.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventField {
+    Topic(usize),
+    Data(usize),
+}
+
+impl EventField {
+    fn read(&self, log: &Log) -> Option<u64> {
+        let word = match self {
+            EventField::Topic(idx) => U256::from_be_bytes(log.topics().get(*idx)?.0),
+            EventField::Data(idx) => {
+                let data = &log.data().data;
+                U256::try_from_be_slice(data.get(idx * 32..(idx + 1) * 32)?)?
+            }
+        };
+
+        word.try_into().ok()
+    }
+}
+
+/// Source-side bridge event, emitted when a message leaves `chain_id`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SourceEvent {
+    pub chain_id: u64,
+    pub contract_address: Option<String>,
+    /// Canonical event signature, e.g. `MessageSent(uint32,uint32,uint256)`.
+    pub signature: String,
+    pub destination_chain_id: EventField,
+    pub message_id: EventField,
+}
+
+impl SourceEvent {
+    pub fn decode(&self, receipt: &TransactionReceipt) -> Vec<BridgeMessage> {
+        let signature_hash: FixedBytes<32> = keccak256(self.signature.as_bytes());
+        let contract_address = self
+            .contract_address
+            .as_ref()
+            .and_then(|address| address.parse::<Address>().ok());
+
+        receipt
+            .inner
+            .logs()
+            .iter()
+            .filter(|log| log.topics().first() == Some(&signature_hash))
+            .filter(|log| contract_address.is_none_or(|address| log.address() == address))
+            .filter_map(|log| {
+                Some(BridgeMessage {
+                    source_chain_id: self.chain_id,
+                    destination_chain_id: self.destination_chain_id.read(log)?,
+                    message_id: self.message_id.read(log)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgeMessage {
+    pub source_chain_id: u64,
+    pub destination_chain_id: u64,
+    pub message_id: u64,
+}
+
+/// Value delivered on the destination chain by a single batch event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgedValue {
+    pub source_chain_id: u64,
+    pub amount: U256,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BridgeEvents {
+    pub sends: Vec<BridgeMessage>,
+    pub deliveries: Vec<BridgeMessage>,
+    pub received: Vec<BridgedValue>,
+}
+
+impl BridgeEvents {
+    pub fn is_empty(&self) -> bool {
+        self.sends.is_empty() && self.deliveries.is_empty()
+    }
+
+    pub fn extend(&mut self, other: BridgeEvents) {
+        self.sends.extend(other.sends);
+        self.deliveries.extend(other.deliveries);
+        self.received.extend(other.received);
+    }
+
+    /// Sums the delivered value per source chain.
+    pub fn received_by_source(&self) -> BTreeMap<u64, U256> {
+        let mut totals = BTreeMap::new();
+        for value in &self.received {
+            let total = totals.entry(value.source_chain_id).or_insert(U256::ZERO);
+            *total = total.saturating_add(value.amount);
+        }
+
+        totals
+    }
+}
+
+/// Decodes bridge sends configured for `chain_id` and batch deliveries
+/// received on `chain_id` from a single receipt.
+pub fn parse_bridge_events(
+    chain_id: u64,
+    receipt: &TransactionReceipt,
+    source_events: &[SourceEvent],
+) -> BridgeEvents {
+    let mut events = BridgeEvents::default();
+
+    for source_event in source_events.iter().filter(|e| e.chain_id == chain_id) {
+        events.sends.extend(source_event.decode(receipt));
+    }
+
+    let signature_bytes: FixedBytes<32> = ETHReceivedFromSourceChainInBatch::SIGNATURE_HASH;
+    for log in receipt.inner.logs() {
+        if log.topics().first() != Some(&signature_bytes) {
+            continue;
+        }
+
+        let primitive_log = PrimitiveLog {
+            address: log.address(),
+            data: log.data().clone(),
+        };
+
+        if let Ok(event) = ETHReceivedFromSourceChainInBatch::decode_log(&primitive_log, false) {
+            events.received.push(BridgedValue {
+                source_chain_id: event.sourceChainId as u64,
+                amount: event
+                    .amounts
+                    .iter()
+                    .fold(U256::ZERO, |total, amount| total.saturating_add(*amount)),
+            });
+            events
+                .deliveries
+                .extend(
+                    (event.startMessageId..event.endMessageId).map(|message_id| BridgeMessage {
+                        source_chain_id: event.sourceChainId as u64,
+                        destination_chain_id: chain_id,
+                        message_id: message_id as u64,
+                    }),
+                );
+        }
+    }
+
+    events
+}
@@ -0,0 +1,271 @@
+use alloy::{
+    primitives::{address, Address, FixedBytes},
+    rpc::types::{eth::Transaction, TransactionReceipt},
+    sol_types::SolEvent,
+};
+
+use crate::{ETHReceivedFromSourceChainInBatch, Tx};
+
+mod erc20 {
+    alloy::sol! {
+        #[derive(Debug)]
+        event Transfer(address indexed from, address indexed to, uint256 value);
+    }
+}
+
+/// A single classification rule. Rules are evaluated in order and the first
+/// one returning `Some` decides the category of the transaction.
+pub trait Classify: Send + Sync {
+    fn classify(&self, tx: &Transaction, receipt: Option<&TransactionReceipt>) -> Option<Tx>;
+}
+
+pub struct Classifier {
+    rules: Vec<Box<dyn Classify>>,
+}
+
+impl Classifier {
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    pub fn with_rule(mut self, rule: impl Classify + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn classify(&self, tx: &Transaction, receipt: Option<&TransactionReceipt>) -> Tx {
+        self.rules
+            .iter()
+            .find_map(|rule| rule.classify(tx, receipt))
+            .unwrap_or(Tx::ContractCall)
+    }
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Self::new()
+            .with_rule(SystemRule)
+            .with_rule(DeploymentRule)
+            .with_rule(NativeTransferRule)
+            .with_rule(BridgeDeliveryRule)
+            .with_rule(TokenTransferRule)
+            .with_rule(ContractCallRule)
+    }
+}
+
+fn is_native_transfer(tx: &Transaction) -> bool {
+    tx.input.is_empty() && tx.to.is_some()
+}
+
+fn has_log(receipt: Option<&TransactionReceipt>, signature: FixedBytes<32>) -> bool {
+    receipt.is_some_and(|receipt| {
+        receipt
+            .inner
+            .logs()
+            .iter()
+            .any(|log| log.topics().first() == Some(&signature))
+    })
+}
+
+/// Deposit transactions (type `0x7e`) and transactions sent by well-known
+/// system accounts.
+pub struct SystemRule;
+
+const DEPOSIT_TX_TYPE: u8 = 0x7e;
+const SYSTEM_ADDRESSES: [Address; 2] = [
+    address!("deaddeaddeaddeaddeaddeaddeaddeaddead0001"),
+    address!("fffffffffffffffffffffffffffffffffffffffe"),
+];
+
+impl Classify for SystemRule {
+    fn classify(&self, tx: &Transaction, _receipt: Option<&TransactionReceipt>) -> Option<Tx> {
+        let is_system =
+            tx.transaction_type == Some(DEPOSIT_TX_TYPE) || SYSTEM_ADDRESSES.contains(&tx.from);
+        is_system.then_some(Tx::System)
+    }
+}
+
+pub struct DeploymentRule;
+
+impl Classify for DeploymentRule {
+    fn classify(&self, tx: &Transaction, _receipt: Option<&TransactionReceipt>) -> Option<Tx> {
+        tx.to.is_none().then_some(Tx::Deployment)
+    }
+}
+
+pub struct NativeTransferRule;
+
+impl Classify for NativeTransferRule {
+    fn classify(&self, tx: &Transaction, _receipt: Option<&TransactionReceipt>) -> Option<Tx> {
+        is_native_transfer(tx).then_some(Tx::Native)
+    }
+}
+
+pub struct BridgeDeliveryRule;
+
+impl Classify for BridgeDeliveryRule {
+    fn classify(&self, _tx: &Transaction, receipt: Option<&TransactionReceipt>) -> Option<Tx> {
+        has_log(receipt, ETHReceivedFromSourceChainInBatch::SIGNATURE_HASH)
+            .then_some(Tx::CrossChain)
+    }
+}
+
+/// ERC-20 style transfers, detected from a `Transfer` log or, without a
+/// receipt, from the `transfer`/`transferFrom` selectors.
+pub struct TokenTransferRule;
+
+const TOKEN_TRANSFER_SELECTORS: [[u8; 4]; 2] = [[0xa9, 0x05, 0x9c, 0xbb], [0x23, 0xb8, 0x72, 0xdd]];
+
+impl Classify for TokenTransferRule {
+    fn classify(&self, tx: &Transaction, receipt: Option<&TransactionReceipt>) -> Option<Tx> {
+        let is_token_transfer = match receipt {
+            Some(_) => has_log(receipt, erc20::Transfer::SIGNATURE_HASH),
+            None => tx
+                .input
+                .get(..4)
+                .is_some_and(|selector| TOKEN_TRANSFER_SELECTORS.iter().any(|s| s == selector)),
+        };
+        is_token_transfer.then_some(Tx::TokenTransfer)
+    }
+}
+
+pub struct ContractCallRule;
+
+impl Classify for ContractCallRule {
+    fn classify(&self, tx: &Transaction, _receipt: Option<&TransactionReceipt>) -> Option<Tx> {
+        tx.to.is_some().then_some(Tx::ContractCall)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        consensus::{Receipt, ReceiptEnvelope, ReceiptWithBloom},
+        primitives::{Address, Bytes, LogData, B256},
+        rpc::types::Log,
+    };
+
+    use super::*;
+
+    const TRANSFER_CALL: [u8; 8] = [0xa9, 0x05, 0x9c, 0xbb, 0, 0, 0, 1];
+    const OTHER_CALL: [u8; 4] = [0x12, 0x34, 0x56, 0x78];
+
+    fn tx(to: Option<Address>, input: &[u8]) -> Transaction {
+        Transaction {
+            from: Address::repeat_byte(0x11),
+            to,
+            input: Bytes::copy_from_slice(input),
+            ..Default::default()
+        }
+    }
+
+    fn call(input: &[u8]) -> Transaction {
+        tx(Some(Address::repeat_byte(0x22)), input)
+    }
+
+    fn receipt(signatures: &[B256]) -> TransactionReceipt {
+        let logs = signatures
+            .iter()
+            .map(|signature| Log {
+                inner: alloy::primitives::Log {
+                    address: Address::repeat_byte(0x33),
+                    data: LogData::new_unchecked(vec![*signature], Bytes::new()),
+                },
+                ..Default::default()
+            })
+            .collect();
+        TransactionReceipt {
+            inner: ReceiptEnvelope::Eip1559(ReceiptWithBloom {
+                receipt: Receipt {
+                    logs,
+                    ..Default::default()
+                },
+                logs_bloom: Default::default(),
+            }),
+            transaction_hash: B256::ZERO,
+            transaction_index: None,
+            block_hash: None,
+            block_number: None,
+            gas_used: 0,
+            effective_gas_price: 0,
+            blob_gas_used: None,
+            blob_gas_price: None,
+            from: Address::ZERO,
+            to: None,
+            contract_address: None,
+            state_root: None,
+            authorization_list: None,
+        }
+    }
+
+    #[test]
+    fn the_first_matching_rule_decides_the_category() {
+        let bridge = ETHReceivedFromSourceChainInBatch::SIGNATURE_HASH;
+        let transfer = erc20::Transfer::SIGNATURE_HASH;
+        let deposit = Transaction {
+            transaction_type: Some(DEPOSIT_TX_TYPE),
+            ..call(&[])
+        };
+        let from_system = Transaction {
+            from: SYSTEM_ADDRESSES[0],
+            ..call(&OTHER_CALL)
+        };
+
+        let cases = [
+            ("deposit type", deposit, None, Tx::System),
+            (
+                "system sender",
+                from_system,
+                Some(receipt(&[transfer])),
+                Tx::System,
+            ),
+            (
+                "creation",
+                tx(None, &OTHER_CALL),
+                Some(receipt(&[transfer])),
+                Tx::Deployment,
+            ),
+            (
+                "empty input",
+                call(&[]),
+                Some(receipt(&[bridge])),
+                Tx::Native,
+            ),
+            (
+                "bridge event",
+                call(&OTHER_CALL),
+                Some(receipt(&[transfer, bridge])),
+                Tx::CrossChain,
+            ),
+            (
+                "transfer log",
+                call(&OTHER_CALL),
+                Some(receipt(&[transfer])),
+                Tx::TokenTransfer,
+            ),
+            (
+                "transfer selector",
+                call(&TRANSFER_CALL),
+                None,
+                Tx::TokenTransfer,
+            ),
+            (
+                "selector with receipt",
+                call(&TRANSFER_CALL),
+                Some(receipt(&[])),
+                Tx::ContractCall,
+            ),
+            ("other call", call(&OTHER_CALL), None, Tx::ContractCall),
+        ];
+
+        let classifier = Classifier::default();
+        for (name, tx, receipt, expected) in cases {
+            assert_eq!(
+                classifier.classify(&tx, receipt.as_ref()),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+}
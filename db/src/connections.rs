@@ -1,21 +1,335 @@
-use diesel::{Connection, PgConnection};
+use diesel::{Connection, PgConnection, SqliteConnection};
 use diesel_async::{
     pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+    sync_connection_wrapper::SyncConnectionWrapper,
     AsyncPgConnection,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use std::{env, sync::Arc, thread, time::Duration};
-use tokio::sync::Mutex;
-use tracing::{error, info};
+use redis::{
+    aio::{ConnectionLike, ConnectionManager},
+    cluster::ClusterClientBuilder,
+    cluster_async::ClusterConnection,
+    Client, Cmd, Pipeline, RedisError, RedisFuture, RedisResult, TlsCertificates, Value,
+};
+use std::{env, fs, sync::Arc, thread, time::Duration};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
 
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations");
+pub(crate) const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("./migrations_sqlite");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedisMode {
+    Standalone,
+    Sentinel,
+    Cluster,
+}
+
+/// Redis connection settings, read from the environment:
+///
+/// - `REDIS_MODE`: `standalone` (default), `sentinel` or `cluster`
+/// - `REDIS_HOSTNAME`: comma-separated `host:port` list of the server, the
+///   sentinels or the cluster seed nodes
+/// - `REDIS_USERNAME`, `REDIS_PASSWORD`: ACL credentials of the data nodes
+/// - `REDIS_SENTINEL_MASTER`, `REDIS_SENTINEL_PASSWORD`: monitored master name
+///   and sentinel password
+/// - `IS_TLS`: connect over TLS, `REDIS_CA_CERT`: PEM file of a custom CA
+#[derive(Debug, Clone)]
+pub struct RedisSettings {
+    pub mode: RedisMode,
+    pub nodes: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub sentinel_master: String,
+    pub sentinel_password: Option<String>,
+    pub tls: bool,
+    pub ca_cert: Option<Vec<u8>>,
+}
+
+impl RedisSettings {
+    pub fn from_env() -> Result<Self, std::io::Error> {
+        let mode = match env::var("REDIS_MODE")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "standalone" => RedisMode::Standalone,
+            "sentinel" => RedisMode::Sentinel,
+            "cluster" => RedisMode::Cluster,
+            other => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid REDIS_MODE: {}", other),
+                ))
+            }
+        };
+        let nodes = env::var("REDIS_HOSTNAME")
+            .unwrap_or("localhost:6379".to_string())
+            .split(',')
+            .map(|node| node.trim().to_string())
+            .filter(|node| !node.is_empty())
+            .collect();
+        let ca_cert = match env::var("REDIS_CA_CERT") {
+            Ok(path) => Some(fs::read(path)?),
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            mode,
+            nodes,
+            username: env::var("REDIS_USERNAME").ok(),
+            password: Some(env::var("REDIS_PASSWORD").unwrap_or("redis".to_string())),
+            sentinel_master: env::var("REDIS_SENTINEL_MASTER").unwrap_or("mymaster".to_string()),
+            sentinel_password: env::var("REDIS_SENTINEL_PASSWORD").ok(),
+            tls: env::var("IS_TLS").is_ok(),
+            ca_cert,
+        })
+    }
+
+    fn url(&self, node: &str, username: Option<&str>, password: Option<&str>) -> String {
+        let scheme = if self.tls { "rediss" } else { "redis" };
+        match password {
+            Some(password) => format!(
+                "{}://{}:{}@{}",
+                scheme,
+                username.unwrap_or_default(),
+                password,
+                node
+            ),
+            None => format!("{}://{}", scheme, node),
+        }
+    }
+
+    fn data_url(&self, node: &str) -> String {
+        self.url(node, self.username.as_deref(), self.password.as_deref())
+    }
+
+    fn certificates(&self) -> Option<TlsCertificates> {
+        self.ca_cert.clone().map(|ca_cert| TlsCertificates {
+            client_tls: None,
+            root_cert: Some(ca_cert),
+        })
+    }
+
+    fn client(&self, url: String) -> RedisResult<Client> {
+        match self.certificates() {
+            Some(certificates) => Client::build_with_tls(url, certificates),
+            None => Client::open(url),
+        }
+    }
+
+    /// Asks the sentinels, in order, for the address of the current master.
+    async fn discover_master(&self) -> RedisResult<String> {
+        for node in &self.nodes {
+            let url = self.url(node, None, self.sentinel_password.as_deref());
+            let mut sentinel = match self.client(url) {
+                Ok(client) => match client.get_multiplexed_tokio_connection().await {
+                    Ok(sentinel) => sentinel,
+                    Err(e) => {
+                        warn!("sentinel {} is unreachable: {}", node, e);
+                        continue;
+                    }
+                },
+                Err(e) => return Err(e),
+            };
+
+            let master: Option<(String, String)> = redis::cmd("SENTINEL")
+                .arg("get-master-addr-by-name")
+                .arg(&self.sentinel_master)
+                .query_async(&mut sentinel)
+                .await
+                .unwrap_or(None);
+            if let Some((host, port)) = master {
+                return Ok(format!("{}:{}", host, port));
+            }
+        }
+
+        Err((
+            redis::ErrorKind::IoError,
+            "no sentinel knows the master",
+            self.sentinel_master.clone(),
+        )
+            .into())
+    }
+}
+
+/// Async Redis connection. Clones share the underlying multiplexed
+/// connections, so callers never wait on each other for a round-trip, and
+/// every mode reconnects after a connection loss.
+#[derive(Clone)]
+pub enum RedisConnection {
+    Standalone(Box<ConnectionManager>),
+    Sentinel(SentinelConnection),
+    Cluster(ClusterConnection),
+}
+
+impl RedisConnection {
+    pub async fn connect(settings: &RedisSettings) -> RedisResult<Self> {
+        match settings.mode {
+            RedisMode::Standalone => {
+                let node = settings
+                    .nodes
+                    .first()
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                let client = settings.client(settings.data_url(node))?;
+                Ok(Self::Standalone(Box::new(
+                    client.get_connection_manager().await?,
+                )))
+            }
+            RedisMode::Sentinel => Ok(Self::Sentinel(SentinelConnection::connect(settings).await?)),
+            RedisMode::Cluster => {
+                let urls: Vec<String> = settings
+                    .nodes
+                    .iter()
+                    .map(|node| settings.data_url(node))
+                    .collect();
+                let mut builder = ClusterClientBuilder::new(urls);
+                if let Some(certificates) = settings.certificates() {
+                    builder = builder.certs(certificates);
+                }
+                Ok(Self::Cluster(
+                    builder.build()?.get_async_connection().await?,
+                ))
+            }
+        }
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            Self::Standalone(conn) => conn.req_packed_command(cmd),
+            Self::Sentinel(conn) => conn.req_packed_command(cmd),
+            Self::Cluster(conn) => conn.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            Self::Standalone(conn) => conn.req_packed_commands(cmd, offset, count),
+            Self::Sentinel(conn) => conn.req_packed_commands(cmd, offset, count),
+            Self::Cluster(conn) => conn.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Self::Standalone(conn) => conn.get_db(),
+            Self::Sentinel(conn) => conn.get_db(),
+            Self::Cluster(conn) => conn.get_db(),
+        }
+    }
+}
 
+/// Connection to the master monitored by the sentinels. Commands that fail
+/// because the master went away or was demoted trigger a new discovery and
+/// are retried once on the new master.
 #[derive(Clone)]
-pub struct DatabaseConnections {
-    pub postgres: Pool<AsyncPgConnection>,
-    pub redis: Arc<Mutex<redis::Connection>>,
+pub struct SentinelConnection {
+    settings: Arc<RedisSettings>,
+    master: Arc<RwLock<(String, ConnectionManager)>>,
+}
+
+impl SentinelConnection {
+    async fn connect(settings: &RedisSettings) -> RedisResult<Self> {
+        let master = Self::connect_master(settings).await?;
+
+        Ok(Self {
+            settings: Arc::new(settings.clone()),
+            master: Arc::new(RwLock::new(master)),
+        })
+    }
+
+    async fn connect_master(settings: &RedisSettings) -> RedisResult<(String, ConnectionManager)> {
+        let address = settings.discover_master().await?;
+        let client = settings.client(settings.data_url(&address))?;
+        let conn = client.get_connection_manager().await?;
+        info!("Connected to Redis master {}", address);
+
+        Ok((address, conn))
+    }
+
+    async fn current(&self) -> ConnectionManager {
+        self.master.read().await.1.clone()
+    }
+
+    /// Reconnects if the sentinels report a different master. Returns whether
+    /// the master changed.
+    async fn failover(&self) -> RedisResult<bool> {
+        let address = self.settings.discover_master().await?;
+        let mut master = self.master.write().await;
+        if master.0 == address {
+            return Ok(false);
+        }
+
+        *master = Self::connect_master(&self.settings).await?;
+        Ok(true)
+    }
+
+    fn needs_failover(e: &RedisError) -> bool {
+        e.is_io_error()
+            || e.is_connection_dropped()
+            || e.is_connection_refusal()
+            || e.kind() == redis::ErrorKind::ReadOnly
+    }
+}
+
+impl ConnectionLike for SentinelConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let mut conn = self.current().await;
+            let result = conn.req_packed_command(cmd).await;
+            match result {
+                Err(e) if Self::needs_failover(&e) => {
+                    if self.failover().await? {
+                        let mut conn = self.current().await;
+                        conn.req_packed_command(cmd).await
+                    } else {
+                        Err(e)
+                    }
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let mut conn = self.current().await;
+            let result = conn.req_packed_commands(cmd, offset, count).await;
+            match result {
+                Err(e) if Self::needs_failover(&e) => {
+                    if self.failover().await? {
+                        let mut conn = self.current().await;
+                        conn.req_packed_commands(cmd, offset, count).await
+                    } else {
+                        Err(e)
+                    }
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
 }
 
+/// Opens the connections of the storage and metrics backends.
+pub struct DatabaseConnections;
+
 impl DatabaseConnections {
     fn run_migrations(db_url: &str) -> Result<(), std::io::Error> {
         for attempt in 1..=MAX_RETRIES {
@@ -41,13 +355,12 @@ impl DatabaseConnections {
         }
 
         error!("Migration failed after {} attempts", MAX_RETRIES);
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        Err(std::io::Error::other(
             "Migration failed after multiple attempts",
         ))
     }
 
-    async fn init_postgres() -> Result<Pool<AsyncPgConnection>, std::io::Error> {
+    pub async fn init_postgres() -> Result<Pool<AsyncPgConnection>, std::io::Error> {
         let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
         let config_db_url = db_url.clone();
         tokio::task::spawn_blocking(move || Self::run_migrations(&db_url.clone())).await??;
@@ -62,27 +375,44 @@ impl DatabaseConnections {
             .expect("Failed to build pool"))
     }
 
-    pub fn init_redis() -> redis::Connection {
-        let redis_host_name: String =
-            env::var("REDIS_HOSTNAME").unwrap_or("localhost:6379".to_string());
-        let redis_password = env::var("REDIS_PASSWORD").unwrap_or("redis".to_string());
-        let uri_scheme = match env::var("IS_TLS") {
-            Ok(_) => "rediss",
-            Err(_) => "redis",
-        };
+    pub async fn init_redis() -> Result<RedisConnection, std::io::Error> {
+        let settings = RedisSettings::from_env()?;
+        for attempt in 1..=MAX_RETRIES {
+            match RedisConnection::connect(&settings).await {
+                Ok(conn) => {
+                    info!("Connected to Redis ({:?})", settings.mode);
+                    return Ok(conn);
+                }
+                Err(e) => error!(
+                    "Redis connection failed on attempt {}/{}: {}",
+                    attempt, MAX_RETRIES, e
+                ),
+            }
 
-        let redis_conn_url = format!("{}://:{}@{}", uri_scheme, redis_password, redis_host_name);
-        redis::Client::open(redis_conn_url)
-            .expect("Invalid connection URL")
-            .get_connection()
-            .expect("failed to connect to Redis")
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+
+        Err(std::io::Error::other(
+            "Redis connection failed after multiple attempts",
+        ))
     }
 
-    pub async fn init() -> Result<Self, std::io::Error> {
-        Ok(Self {
-            postgres: Self::init_postgres().await?,
-            redis: Arc::new(Mutex::new(Self::init_redis())),
+    /// Opens the SQLite file at `DATABASE_URL`, `indexer.db` by default, and
+    /// runs its migrations.
+    pub async fn init_sqlite() -> Result<SyncConnectionWrapper<SqliteConnection>, std::io::Error> {
+        let db_path = env::var("DATABASE_URL").unwrap_or("indexer.db".to_string());
+        let conn = tokio::task::spawn_blocking(move || {
+            let mut conn = SqliteConnection::establish(&db_path)
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+            conn.run_pending_migrations(SQLITE_MIGRATIONS)
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+            info!("Ran SQLite migration successfully");
+
+            Ok::<_, std::io::Error>(conn)
         })
+        .await??;
+
+        Ok(SyncConnectionWrapper::new(conn))
     }
 }
 
@@ -1,13 +1,18 @@
+pub mod bridge;
+pub mod classifier;
 pub mod connections;
 pub mod models;
 pub mod providers;
 pub mod schema;
 pub mod types;
 
+pub use bridge::*;
+pub use classifier::*;
 pub use connections::*;
 pub use models::*;
 pub use providers::*;
 pub use schema::*;
 pub use types::*;
 
+pub use chrono::FixedOffset;
 pub use redis::RedisError;
@@ -1,10 +1,15 @@
-use crate::{types::ConvertToHex, Tx};
+use crate::{to_unix_ms, types::ConvertToHex, BlockCounts, BridgeMessage, Tx, TxCounts};
 use alloy::{
-    primitives::{Address, FixedBytes, Uint, U256},
+    consensus::Transaction as _,
+    consensus::TxEnvelope,
+    eips::eip2718::{Decodable2718, Encodable2718},
+    primitives::{Address, Bytes, FixedBytes, U256},
     rpc::types::eth::{Parity, Signature, Transaction as AlloyTx},
 };
+use bigdecimal::BigDecimal;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, str::FromStr};
 
 #[derive(
     Clone,
@@ -21,7 +26,7 @@ use serde::{Deserialize, Serialize};
 )]
 #[diesel(primary_key(chain_id))]
 #[diesel(table_name = crate::schema::chains)]
-#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(check_for_backend(diesel::pg::Pg, diesel::sqlite::Sqlite))]
 pub struct Chain {
     pub chain_id: i64,
     pub latest_tps: i64,
@@ -37,99 +42,280 @@ pub struct Chain {
     Serialize,
     Deserialize,
 )]
-#[diesel(primary_key(chain_id, transaction_hash))]
+#[diesel(primary_key(chain_id, block_number, transaction_hash))]
 #[diesel(belongs_to(Chain, foreign_key = chain_id))]
 #[diesel(table_name = crate::schema::transactions)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct TxModel {
     pub chain_id: i64,
-    pub transaction_hash: String,
-    pub transaction_nonce: String,
-    pub block_hash: Option<String>,
-    pub block_number: Option<i64>,
+    pub transaction_hash: Vec<u8>,
+    pub transaction_nonce: i64,
+    pub block_hash: Option<Vec<u8>>,
+    pub block_number: i64,
     pub transaction_index: Option<i64>,
-    pub _from: String,
-    pub _to: Option<String>,
-    pub value: String,
-    pub gas_price: Option<String>,
-    pub gas: String,
-    pub input: String,
+    pub _from: Vec<u8>,
+    pub _to: Option<Vec<u8>>,
+    pub value: BigDecimal,
+    pub gas_price: Option<BigDecimal>,
+    pub gas: i64,
+    pub input: Vec<u8>,
     pub v: String,
     pub r: String,
     pub s: String,
     pub transaction_type: String,
     pub impersonated: bool,
-    pub max_priority_fee_per_gas: Option<String>,
-    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<BigDecimal>,
+    pub max_fee_per_gas: Option<BigDecimal>,
     pub tx_type: String,
+    pub access_list: Option<serde_json::Value>,
+    pub max_fee_per_blob_gas: Option<BigDecimal>,
+    pub blob_versioned_hashes: Option<serde_json::Value>,
+    pub authorization_list: Option<serde_json::Value>,
+    pub y_parity: Option<bool>,
+    /// EIP-2718 envelope of the signed transaction.
+    pub raw_transaction: Option<Vec<u8>>,
+}
+
+/// Converts a uint256 to a `NUMERIC` value.
+pub fn to_numeric(value: U256) -> BigDecimal {
+    BigDecimal::from_str(&value.to_string()).unwrap_or_default()
+}
+
+/// Converts a `NUMERIC` value back to a uint256, or zero if it does not fit.
+pub fn from_numeric(value: &BigDecimal) -> U256 {
+    try_from_numeric(value).unwrap_or(U256::ZERO)
+}
+
+/// Converts a `NUMERIC` value back to a uint256, if it is a non-negative
+/// integer that fits.
+pub fn try_from_numeric(value: &BigDecimal) -> Option<U256> {
+    if !value.is_integer() {
+        return None;
+    }
+    let (digits, _) = value.with_scale(0).into_bigint_and_exponent();
+    U256::from_str(&digits.to_string()).ok()
+}
+
+/// Stored transaction that cannot be turned back into its RPC form.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ConversionError {
+    #[error("missing {0}")]
+    MissingField(&'static str),
+    #[error("invalid {0}")]
+    InvalidField(&'static str),
+}
+
+fn numeric_field<T: TryFrom<U256>>(
+    value: &BigDecimal,
+    field: &'static str,
+) -> Result<T, ConversionError> {
+    try_from_numeric(value)
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or(ConversionError::InvalidField(field))
+}
+
+fn integer_field<T: TryFrom<i64>>(value: i64, field: &'static str) -> Result<T, ConversionError> {
+    T::try_from(value).map_err(|_| ConversionError::InvalidField(field))
+}
+
+fn json_field<T: serde::de::DeserializeOwned>(
+    value: serde_json::Value,
+    field: &'static str,
+) -> Result<T, ConversionError> {
+    serde_json::from_value(value).map_err(|_| ConversionError::InvalidField(field))
+}
+
+fn hash_field(value: &[u8], field: &'static str) -> Result<FixedBytes<32>, ConversionError> {
+    FixedBytes::try_from(value).map_err(|_| ConversionError::InvalidField(field))
+}
+
+fn address_field(value: &[u8], field: &'static str) -> Result<Address, ConversionError> {
+    Address::try_from(value).map_err(|_| ConversionError::InvalidField(field))
 }
 
-impl From<TxModel> for AlloyTx {
-    fn from(value: TxModel) -> Self {
-        let v = value.v.parse().unwrap_or(Uint::default());
-        let signature = Some(Signature {
-            r: value.r.parse().unwrap_or(Uint::default()),
-            s: value.s.parse().unwrap_or(Uint::default()),
-            v,
-            y_parity: if v.to::<u64>() < 2 {
-                Some(Parity(v.to()))
-            } else {
+/// Replaces the signed fields of `tx` with those of its stored EIP-2718
+/// envelope. The block context, sender and effective gas price of EIP-1559
+/// style transactions are not part of the envelope and stay as stored.
+fn apply_envelope(tx: &mut AlloyTx, raw: &[u8]) -> Result<(), ConversionError> {
+    let invalid = ConversionError::InvalidField("raw_transaction");
+    let envelope = TxEnvelope::decode_2718(&mut &raw[..]).map_err(|_| invalid.clone())?;
+    if *envelope.tx_hash() != tx.hash {
+        return Err(invalid);
+    }
+    let signature = match &envelope {
+        TxEnvelope::Legacy(signed) => *signed.signature(),
+        TxEnvelope::Eip2930(signed) => *signed.signature(),
+        TxEnvelope::Eip1559(signed) => *signed.signature(),
+        TxEnvelope::Eip4844(signed) => *signed.signature(),
+        TxEnvelope::Eip7702(signed) => *signed.signature(),
+        _ => return Err(invalid),
+    };
+
+    tx.nonce = envelope.nonce();
+    tx.to = envelope.to().to().copied();
+    tx.value = envelope.value();
+    tx.gas = envelope.gas_limit();
+    tx.input = Bytes::copy_from_slice(envelope.input());
+    tx.chain_id = envelope.chain_id();
+    tx.transaction_type = Some(envelope.ty());
+    if let Some(gas_price) = envelope.gas_price() {
+        tx.gas_price = Some(gas_price);
+    }
+    tx.max_fee_per_gas = envelope
+        .gas_price()
+        .is_none()
+        .then(|| envelope.max_fee_per_gas());
+    tx.max_priority_fee_per_gas = envelope.max_priority_fee_per_gas();
+    tx.max_fee_per_blob_gas = envelope.max_fee_per_blob_gas();
+    tx.access_list = envelope.access_list().cloned();
+    tx.blob_versioned_hashes = envelope.blob_versioned_hashes().map(<[_]>::to_vec);
+    tx.authorization_list = envelope.authorization_list().map(<[_]>::to_vec);
+    // typed transactions sign the parity alone, legacy ones fold in the chain
+    tx.signature = Some(if envelope.is_legacy() {
+        Signature {
+            r: signature.r(),
+            s: signature.s(),
+            v: U256::from(signature.v().to_u64()),
+            y_parity: None,
+        }
+    } else {
+        Signature {
+            r: signature.r(),
+            s: signature.s(),
+            v: U256::from(signature.v().y_parity() as u8),
+            y_parity: Some(Parity(signature.v().y_parity())),
+        }
+    });
+
+    Ok(())
+}
+
+/// Rebuilds the transaction as returned by `eth_getTransactionByHash`, with
+/// the signed fields taken from the stored envelope when there is one.
+impl TryFrom<TxModel> for AlloyTx {
+    type Error = ConversionError;
+
+    fn try_from(mut value: TxModel) -> Result<Self, Self::Error> {
+        let raw_transaction = value.raw_transaction.take();
+        // unsigned transactions are stored with empty signature values
+        let signature = if value.v.is_empty() && value.r.is_empty() && value.s.is_empty() {
+            None
+        } else {
+            let v: U256 = value
+                .v
+                .parse()
+                .map_err(|_| ConversionError::InvalidField("v"))?;
+            // rows indexed without `y_parity` have it in `v` for typed
+            // transactions
+            let y_parity = value
+                .y_parity
+                .or_else(|| (v < U256::from(2)).then(|| v == U256::from(1)));
+            Some(Signature {
+                r: value
+                    .r
+                    .parse()
+                    .map_err(|_| ConversionError::InvalidField("r"))?,
+                s: value
+                    .s
+                    .parse()
+                    .map_err(|_| ConversionError::InvalidField("s"))?,
+                v,
+                y_parity: y_parity.map(Parity),
+            })
+        };
+        // pre-EIP-155 legacy transactions are not bound to a chain
+        let chain_id = match &signature {
+            Some(signature) if signature.v == U256::from(27) || signature.v == U256::from(28) => {
                 None
-            },
-        });
-        Self {
-            hash: value.transaction_hash.parse().unwrap_or(FixedBytes::ZERO),
-            nonce: value
-                .transaction_nonce
-                .parse::<U256>()
-                .unwrap_or_default()
-                .to(),
+            }
+            _ => Some(integer_field(value.chain_id, "chain_id")?),
+        };
+        let transaction_type = if value.transaction_type.is_empty() {
+            None
+        } else {
+            Some(
+                value
+                    .transaction_type
+                    .parse::<U256>()
+                    .ok()
+                    .and_then(|x| u8::try_from(x).ok())
+                    .ok_or(ConversionError::InvalidField("transaction_type"))?,
+            )
+        };
+
+        let mut tx = Self {
+            hash: hash_field(&value.transaction_hash, "transaction_hash")?,
+            nonce: integer_field(value.transaction_nonce, "transaction_nonce")?,
             block_hash: value
                 .block_hash
-                .map(|x| x.parse().unwrap_or(FixedBytes::ZERO)),
-            block_number: Some(value.block_number.unwrap_or(0) as u64),
-            transaction_index: Some(value.transaction_index.unwrap() as u64),
-            from: value._from.parse().unwrap_or(Address::ZERO),
-            to: value._to.map(|x| x.parse().unwrap_or(Address::ZERO)),
-            value: value.value.parse().unwrap(),
-            gas_price: value.gas_price.map(|x| x.parse::<U256>().unwrap().to()),
-            gas: value.gas.parse::<U256>().unwrap().to(),
-            input: value.input.parse().unwrap(),
-            transaction_type: if value.transaction_type.is_empty() {
-                None
-            } else {
-                Some(value.transaction_type.parse::<U256>().unwrap().to())
-            },
-            access_list: Some(Default::default()),
+                .map(|x| hash_field(&x, "block_hash"))
+                .transpose()?,
+            block_number: Some(integer_field(value.block_number, "block_number")?),
+            transaction_index: value
+                .transaction_index
+                .map(|x| integer_field(x, "transaction_index"))
+                .transpose()?,
+            from: address_field(&value._from, "_from")?,
+            to: value._to.map(|x| address_field(&x, "_to")).transpose()?,
+            value: numeric_field(&value.value, "value")?,
+            gas_price: value
+                .gas_price
+                .map(|x| numeric_field(&x, "gas_price"))
+                .transpose()?,
+            gas: integer_field(value.gas, "gas")?,
+            input: Bytes::from(value.input),
+            transaction_type,
+            access_list: value
+                .access_list
+                .map(|x| json_field(x, "access_list"))
+                .transpose()?,
             max_priority_fee_per_gas: value
                 .max_priority_fee_per_gas
-                .map(|x| x.parse::<U256>().unwrap_or(U256::ZERO).to()),
+                .map(|x| numeric_field(&x, "max_priority_fee_per_gas"))
+                .transpose()?,
             max_fee_per_gas: value
                 .max_fee_per_gas
-                .map(|x| x.parse::<U256>().unwrap_or(U256::ZERO).to()),
-            chain_id: Some(value.chain_id as u64),
+                .map(|x| numeric_field(&x, "max_fee_per_gas"))
+                .transpose()?,
+            max_fee_per_blob_gas: value
+                .max_fee_per_blob_gas
+                .map(|x| numeric_field(&x, "max_fee_per_blob_gas"))
+                .transpose()?,
+            blob_versioned_hashes: value
+                .blob_versioned_hashes
+                .map(|x| json_field(x, "blob_versioned_hashes"))
+                .transpose()?,
+            authorization_list: value
+                .authorization_list
+                .map(|x| json_field(x, "authorization_list"))
+                .transpose()?,
+            chain_id,
             signature,
-            ..Default::default()
+        };
+        if let Some(raw) = raw_transaction {
+            apply_envelope(&mut tx, &raw)?;
         }
+
+        Ok(tx)
     }
 }
 
 impl TxModel {
-    pub fn from(chain_id: u64, value: &AlloyTx, tx_type: &Tx) -> Self {
-        let result = Self {
+    pub fn from(chain_id: u64, value: AlloyTx, tx_type: &Tx) -> Self {
+        let mut result = Self {
             tx_type: tx_type.to_string(),
-            chain_id: chain_id.try_into().unwrap(),
-            transaction_hash: value.hash.to_hex_string(),
-            transaction_nonce: value.nonce.to_hex_string(),
-            block_hash: value.block_hash.map(|x| x.to_hex_string()),
-            block_number: value.block_number.map(|x| x as i64),
+            chain_id: chain_id as i64,
+            transaction_hash: value.hash.to_vec(),
+            transaction_nonce: value.nonce as i64,
+            block_hash: value.block_hash.map(|x| x.to_vec()),
+            block_number: value.block_number.unwrap_or_default() as i64,
             transaction_index: value.transaction_index.map(|x| x as i64),
-            _from: value.from.to_hex_string(),
-            _to: value.to.map(|x| x.to_hex_string()),
-            value: value.value.to_hex_string(),
-            gas_price: value.gas_price.map(|x| x.to_hex_string()),
-            gas: value.gas.to_hex_string(),
-            input: value.input.to_hex_string(),
+            _from: value.from.to_vec(),
+            _to: value.to.map(|x| x.to_vec()),
+            value: to_numeric(value.value),
+            gas_price: value.gas_price.map(|x| to_numeric(U256::from(x))),
+            gas: value.gas as i64,
+            input: value.input.to_vec(),
             v: value
                 .signature
                 .map(|sign| sign.v.to_hex_string())
@@ -147,9 +333,635 @@ impl TxModel {
                 .map(|x| x.to_hex_string())
                 .unwrap_or_default(),
             impersonated: false,
-            max_priority_fee_per_gas: value.max_priority_fee_per_gas.map(|x| x.to_hex_string()),
-            max_fee_per_gas: value.max_fee_per_gas.map(|x| x.to_hex_string()),
+            max_priority_fee_per_gas: value
+                .max_priority_fee_per_gas
+                .map(|x| to_numeric(U256::from(x))),
+            max_fee_per_gas: value.max_fee_per_gas.map(|x| to_numeric(U256::from(x))),
+            access_list: value
+                .access_list
+                .as_ref()
+                .and_then(|x| serde_json::to_value(x).ok()),
+            max_fee_per_blob_gas: value
+                .max_fee_per_blob_gas
+                .map(|x| to_numeric(U256::from(x))),
+            blob_versioned_hashes: value
+                .blob_versioned_hashes
+                .as_ref()
+                .and_then(|x| serde_json::to_value(x).ok()),
+            authorization_list: value
+                .authorization_list
+                .as_ref()
+                .and_then(|x| serde_json::to_value(x).ok()),
+            y_parity: value
+                .signature
+                .and_then(|sign| sign.y_parity)
+                .map(|parity| parity.0),
+            raw_transaction: None,
         };
+        // kept only when the fields reproduce the signed transaction
+        let hash = value.hash;
+        result.raw_transaction = TxEnvelope::try_from(value)
+            .ok()
+            .filter(|envelope| *envelope.tx_hash() == hash)
+            .map(|envelope| envelope.encoded_2718());
         result
     }
 }
+
+#[derive(Clone, Debug, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::bridge_messages)]
+#[diesel(check_for_backend(diesel::pg::Pg, diesel::sqlite::Sqlite))]
+pub struct BridgeSendModel {
+    pub source_chain_id: i64,
+    pub destination_chain_id: i64,
+    pub message_id: i64,
+    pub sent_block: Option<i64>,
+    pub sent_at: Option<i64>,
+}
+
+#[derive(Clone, Debug, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::bridge_messages)]
+#[diesel(check_for_backend(diesel::pg::Pg, diesel::sqlite::Sqlite))]
+pub struct BridgeDeliveryModel {
+    pub source_chain_id: i64,
+    pub destination_chain_id: i64,
+    pub message_id: i64,
+    pub delivered_block: Option<i64>,
+    pub delivered_at: Option<i64>,
+}
+
+#[derive(Clone, Debug, QueryableByName, Serialize, Deserialize)]
+pub struct DeliveryLatency {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub source_chain_id: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub destination_chain_id: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub messages: i64,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub p50: f64,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub p90: f64,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub p99: f64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub max: i64,
+}
+
+/// Per-block metrics, persisted so the metric series can be rebuilt.
+#[derive(Clone, Debug, PartialEq, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::blocks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BlockModel {
+    pub chain_id: i64,
+    pub block_number: i64,
+    pub block_hash: String,
+    pub block_timestamp: i64,
+    pub tx_count: i64,
+    pub counts: serde_json::Value,
+    pub bridged: serde_json::Value,
+}
+
+impl BlockModel {
+    pub fn new(
+        chain_id: u64,
+        block_number: u64,
+        block_hash: String,
+        timestamp: i64,
+        tx_count: usize,
+        category_counts: &BTreeMap<Tx, TxCounts>,
+        received_by_source: &BTreeMap<u64, U256>,
+    ) -> Self {
+        let counts: BTreeMap<String, TxCounts> = category_counts
+            .iter()
+            .map(|(category, counts)| (category.to_string(), *counts))
+            .collect();
+
+        Self {
+            chain_id: chain_id as i64,
+            block_number: block_number as i64,
+            block_hash,
+            block_timestamp: to_unix_ms(timestamp) / 1000,
+            tx_count: tx_count as i64,
+            counts: serde_json::to_value(counts).unwrap_or_default(),
+            bridged: serde_json::to_value(received_by_source).unwrap_or_default(),
+        }
+    }
+
+    /// Counts per category. Categories this build does not know are skipped.
+    pub fn category_counts(&self) -> BTreeMap<Tx, TxCounts> {
+        serde_json::from_value::<BTreeMap<String, TxCounts>>(self.counts.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(category, counts)| Some((Tx::from_str(&category).ok()?, counts)))
+            .collect()
+    }
+
+    pub fn block_counts(&self) -> BlockCounts {
+        BlockCounts {
+            chain_id: self.chain_id as u64,
+            height: self.block_number as u64,
+            timestamp: self.block_timestamp,
+            tx_count: self.tx_count as u64,
+            category_counts: self.category_counts(),
+            received_by_source: self.received_by_source(),
+        }
+    }
+
+    pub fn received_by_source(&self) -> BTreeMap<u64, U256> {
+        serde_json::from_value(self.bridged.clone()).unwrap_or_default()
+    }
+
+    /// Rollup rows of the block: one per category, plus the `Tx::All` row
+    /// counting every transaction of the block.
+    pub fn rollup_rows(&self) -> Vec<(Tx, TxCounts)> {
+        let category_counts = self.category_counts();
+        let all = category_counts.values().fold(
+            TxCounts {
+                total: self.tx_count as u64,
+                ..TxCounts::default()
+            },
+            |all, counts| TxCounts {
+                total: all.total,
+                failed: all.failed + counts.failed,
+                gas_used: all.gas_used + counts.gas_used,
+                value: all.value.saturating_add(counts.value),
+            },
+        );
+
+        Tx::CATEGORIES
+            .iter()
+            .map(|category| {
+                let counts = category_counts.get(category).copied().unwrap_or_default();
+                (category.clone(), counts)
+            })
+            .chain(std::iter::once((Tx::All, all)))
+            .collect()
+    }
+}
+
+/// Write that failed after every retry of the write queue.
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = crate::schema::dead_letters)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DeadLetterModel {
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub error: String,
+    pub attempts: i32,
+}
+
+/// Block the indexer failed to process. `failed_at` is the unix second of the
+/// last failure.
+#[derive(Clone, Debug, QueryableByName, Serialize, Deserialize)]
+pub struct FailedBlockModel {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub chain_id: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub block_number: i64,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub error: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub attempts: i32,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub skipped: bool,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub failed_at: i64,
+}
+
+#[derive(Clone, Debug, QueryableByName)]
+pub struct RollupVolume {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub idx: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub tx_count: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub successful: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub failed: i64,
+}
+
+impl BridgeSendModel {
+    pub fn new(message: &BridgeMessage, block_number: u64, timestamp: i64) -> Self {
+        Self {
+            source_chain_id: message.source_chain_id as i64,
+            destination_chain_id: message.destination_chain_id as i64,
+            message_id: message.message_id as i64,
+            sent_block: Some(block_number as i64),
+            sent_at: Some(timestamp),
+        }
+    }
+}
+
+impl BridgeDeliveryModel {
+    pub fn new(message: &BridgeMessage, block_number: u64, timestamp: i64) -> Self {
+        Self {
+            source_chain_id: message.source_chain_id as i64,
+            destination_chain_id: message.destination_chain_id as i64,
+            message_id: message.message_id as i64,
+            delivered_block: Some(block_number as i64),
+            delivered_at: Some(timestamp),
+        }
+    }
+}
+
+#[cfg(test)]
+impl TxModel {
+    /// Native transfer at position `index` of block `block_number`.
+    pub(crate) fn fixture(chain_id: i64, block_number: i64, index: i64) -> Self {
+        let mut transaction_hash = vec![0; 32];
+        transaction_hash[..8].copy_from_slice(&block_number.to_be_bytes());
+        transaction_hash[8..16].copy_from_slice(&index.to_be_bytes());
+
+        Self {
+            chain_id,
+            transaction_hash,
+            transaction_nonce: index,
+            block_hash: Some(vec![1; 32]),
+            block_number,
+            transaction_index: Some(index),
+            _from: vec![2; 20],
+            _to: Some(vec![3; 20]),
+            value: BigDecimal::from(1_000_000_000_000_000_000u64),
+            gas_price: Some(BigDecimal::from(1_000_000_000u64)),
+            gas: 21_000,
+            input: Vec::new(),
+            v: "0x1b".to_string(),
+            r: "0x1".to_string(),
+            s: "0x1".to_string(),
+            transaction_type: "0x0".to_string(),
+            impersonated: false,
+            max_priority_fee_per_gas: None,
+            max_fee_per_gas: None,
+            tx_type: Tx::Native.to_string(),
+            access_list: None,
+            max_fee_per_blob_gas: None,
+            blob_versioned_hashes: None,
+            authorization_list: None,
+            y_parity: None,
+            raw_transaction: None,
+        }
+    }
+}
+
+#[cfg(test)]
+// alloy 0.3 signs transactions with the signature type deprecated by the
+// alloy-primitives release in the lockfile
+#[allow(deprecated)]
+mod tests {
+    use super::*;
+    use alloy::{
+        consensus::{SignableTransaction, TxEip1559, TxEip2930, TxEip4844, TxEip7702, TxLegacy},
+        eips::{
+            eip2930::{AccessList, AccessListItem},
+            eip7702::Authorization,
+        },
+        primitives::{Parity as SignatureParity, Signature as PrimitiveSignature, TxKind, B256},
+    };
+
+    fn primitive_signature(parity: SignatureParity) -> PrimitiveSignature {
+        PrimitiveSignature::from_rs_and_parity(
+            U256::from_be_bytes([0x1a; 32]),
+            U256::from_be_bytes([0x2b; 32]) >> 2,
+            parity,
+        )
+        .unwrap()
+    }
+
+    fn typed_signature() -> Signature {
+        let signature = primitive_signature(SignatureParity::Parity(true));
+        Signature {
+            r: signature.r(),
+            s: signature.s(),
+            v: U256::from(1),
+            y_parity: Some(Parity(true)),
+        }
+    }
+
+    fn access_list() -> AccessList {
+        AccessList(vec![AccessListItem {
+            address: Address::repeat_byte(0x44),
+            storage_keys: vec![B256::repeat_byte(0x55)],
+        }])
+    }
+
+    /// Transaction as a node returns it from `eth_getTransactionByHash`.
+    fn mined(hash: B256) -> AlloyTx {
+        AlloyTx {
+            hash,
+            block_hash: Some(B256::repeat_byte(0xbb)),
+            block_number: Some(100),
+            transaction_index: Some(3),
+            from: Address::repeat_byte(0x11),
+            ..Default::default()
+        }
+    }
+
+    fn legacy(chain_id: Option<u64>) -> AlloyTx {
+        let tx = TxLegacy {
+            chain_id,
+            nonce: 7,
+            gas_price: 20_000_000_000,
+            gas_limit: 21_000,
+            to: TxKind::Call(Address::repeat_byte(0x22)),
+            value: U256::from(1_000_000_000_000_000_000u64),
+            input: Bytes::new(),
+        };
+        let v = match chain_id {
+            Some(chain_id) => chain_id * 2 + 36,
+            None => 28,
+        };
+        let signed = tx
+            .clone()
+            .into_signed(primitive_signature(SignatureParity::try_from(v).unwrap()));
+        let signature = signed.signature();
+
+        AlloyTx {
+            nonce: tx.nonce,
+            to: tx.to.to().copied(),
+            value: tx.value,
+            gas_price: Some(tx.gas_price),
+            gas: tx.gas_limit,
+            input: tx.input,
+            signature: Some(Signature {
+                r: signature.r(),
+                s: signature.s(),
+                v: U256::from(v),
+                y_parity: None,
+            }),
+            chain_id,
+            transaction_type: Some(0),
+            ..mined(*signed.hash())
+        }
+    }
+
+    fn eip2930() -> AlloyTx {
+        let tx = TxEip2930 {
+            chain_id: 1,
+            nonce: 8,
+            gas_price: 20_000_000_000,
+            gas_limit: 50_000,
+            to: TxKind::Call(Address::repeat_byte(0x22)),
+            value: U256::ZERO,
+            access_list: access_list(),
+            input: Bytes::from(vec![0xa9, 0x05, 0x9c, 0xbb]),
+        };
+        let signed = tx
+            .clone()
+            .into_signed(primitive_signature(SignatureParity::Parity(true)));
+
+        AlloyTx {
+            nonce: tx.nonce,
+            to: tx.to.to().copied(),
+            value: tx.value,
+            gas_price: Some(tx.gas_price),
+            gas: tx.gas_limit,
+            input: tx.input,
+            signature: Some(typed_signature()),
+            chain_id: Some(tx.chain_id),
+            access_list: Some(tx.access_list),
+            transaction_type: Some(1),
+            ..mined(*signed.hash())
+        }
+    }
+
+    fn eip1559() -> AlloyTx {
+        let tx = TxEip1559 {
+            chain_id: 1,
+            nonce: 9,
+            gas_limit: 60_000,
+            max_fee_per_gas: 30_000_000_000,
+            max_priority_fee_per_gas: 2_000_000_000,
+            to: TxKind::Create,
+            value: U256::from(5),
+            access_list: AccessList::default(),
+            input: Bytes::from(vec![0x60, 0x80, 0x60, 0x40]),
+        };
+        let signed = tx
+            .clone()
+            .into_signed(primitive_signature(SignatureParity::Parity(true)));
+
+        AlloyTx {
+            nonce: tx.nonce,
+            to: None,
+            value: tx.value,
+            // effective price paid in the block
+            gas_price: Some(17_000_000_000),
+            gas: tx.gas_limit,
+            max_fee_per_gas: Some(tx.max_fee_per_gas),
+            max_priority_fee_per_gas: Some(tx.max_priority_fee_per_gas),
+            input: tx.input,
+            signature: Some(typed_signature()),
+            chain_id: Some(tx.chain_id),
+            access_list: Some(tx.access_list),
+            transaction_type: Some(2),
+            ..mined(*signed.hash())
+        }
+    }
+
+    fn eip4844() -> AlloyTx {
+        let tx = TxEip4844 {
+            chain_id: 1,
+            nonce: 10,
+            gas_limit: 100_000,
+            max_fee_per_gas: 30_000_000_000,
+            max_priority_fee_per_gas: 2_000_000_000,
+            to: Address::repeat_byte(0x33),
+            value: U256::ZERO,
+            access_list: AccessList::default(),
+            blob_versioned_hashes: vec![B256::repeat_byte(0x01)],
+            max_fee_per_blob_gas: 1_000_000,
+            input: Bytes::new(),
+        };
+        let signed = tx
+            .clone()
+            .into_signed(primitive_signature(SignatureParity::Parity(true)));
+
+        AlloyTx {
+            nonce: tx.nonce,
+            to: Some(tx.to),
+            value: tx.value,
+            gas_price: Some(17_000_000_000),
+            gas: tx.gas_limit,
+            max_fee_per_gas: Some(tx.max_fee_per_gas),
+            max_priority_fee_per_gas: Some(tx.max_priority_fee_per_gas),
+            max_fee_per_blob_gas: Some(tx.max_fee_per_blob_gas),
+            input: tx.input,
+            signature: Some(typed_signature()),
+            chain_id: Some(tx.chain_id),
+            blob_versioned_hashes: Some(tx.blob_versioned_hashes),
+            access_list: Some(tx.access_list),
+            transaction_type: Some(3),
+            ..mined(*signed.hash())
+        }
+    }
+
+    fn eip7702() -> AlloyTx {
+        let authorization = Authorization {
+            chain_id: U256::from(1),
+            address: Address::repeat_byte(0x66),
+            nonce: 0,
+        }
+        .into_signed(primitive_signature(SignatureParity::Parity(false)));
+        let tx = TxEip7702 {
+            chain_id: 1,
+            nonce: 11,
+            gas_limit: 80_000,
+            max_fee_per_gas: 30_000_000_000,
+            max_priority_fee_per_gas: 2_000_000_000,
+            to: Address::repeat_byte(0x11),
+            value: U256::ZERO,
+            access_list: AccessList::default(),
+            authorization_list: vec![authorization],
+            input: Bytes::new(),
+        };
+        let signed = tx
+            .clone()
+            .into_signed(primitive_signature(SignatureParity::Parity(true)));
+
+        AlloyTx {
+            nonce: tx.nonce,
+            to: Some(tx.to),
+            value: tx.value,
+            gas_price: Some(17_000_000_000),
+            gas: tx.gas_limit,
+            max_fee_per_gas: Some(tx.max_fee_per_gas),
+            max_priority_fee_per_gas: Some(tx.max_priority_fee_per_gas),
+            input: tx.input,
+            signature: Some(typed_signature()),
+            chain_id: Some(tx.chain_id),
+            access_list: Some(tx.access_list),
+            transaction_type: Some(4),
+            authorization_list: Some(tx.authorization_list),
+            ..mined(*signed.hash())
+        }
+    }
+
+    fn transactions() -> Vec<(&'static str, AlloyTx)> {
+        vec![
+            ("legacy pre-EIP-155", legacy(None)),
+            ("legacy EIP-155", legacy(Some(1))),
+            ("EIP-2930", eip2930()),
+            ("EIP-1559", eip1559()),
+            ("EIP-4844", eip4844()),
+            ("EIP-7702", eip7702()),
+        ]
+    }
+
+    #[test]
+    fn stored_envelopes_are_served_as_fetched() {
+        for (name, tx) in transactions() {
+            let model = TxModel::from(1, tx.clone(), &Tx::Native);
+            let raw = model.raw_transaction.clone();
+            assert!(raw.is_some(), "{} has no envelope", name);
+
+            let served = AlloyTx::try_from(model).unwrap();
+            assert_eq!(
+                serde_json::to_string(&served).unwrap(),
+                serde_json::to_string(&tx).unwrap(),
+                "{}",
+                name
+            );
+            let envelope = TxEnvelope::try_from(served).unwrap();
+            assert_eq!(Some(envelope.encoded_2718()), raw, "{}", name);
+        }
+    }
+
+    #[test]
+    fn signed_fields_are_read_from_the_envelope() {
+        for (name, tx) in transactions() {
+            let mut model = TxModel::from(1, tx.clone(), &Tx::Native);
+            model.transaction_nonce = 0;
+            model.input = vec![0xff];
+            model.access_list = None;
+            model.max_fee_per_gas = None;
+
+            assert_eq!(AlloyTx::try_from(model).unwrap(), tx, "{}", name);
+        }
+    }
+
+    #[test]
+    fn envelopes_of_other_transactions_are_rejected() {
+        let mut model = TxModel::from(1, eip1559(), &Tx::Native);
+        model.raw_transaction = TxModel::from(1, eip2930(), &Tx::Native).raw_transaction;
+
+        assert_eq!(
+            AlloyTx::try_from(model).unwrap_err(),
+            ConversionError::InvalidField("raw_transaction")
+        );
+    }
+
+    #[test]
+    fn transactions_round_trip_through_their_columns() {
+        for (name, tx) in transactions() {
+            let mut model = TxModel::from(1, tx.clone(), &Tx::Native);
+            // rows indexed before the envelope was stored
+            model.raw_transaction = None;
+
+            assert_eq!(AlloyTx::try_from(model).unwrap(), tx, "{}", name);
+        }
+    }
+
+    #[test]
+    fn malformed_columns_are_reported() {
+        type Corruption = fn(&mut TxModel);
+        let cases: [(&str, Corruption); 22] = [
+            ("v", |model| model.v = "0xzz".to_string()),
+            ("r", |model| model.r = "r".to_string()),
+            ("s", |model| model.s = "-1".to_string()),
+            ("chain_id", |model| model.chain_id = -1),
+            ("transaction_type", |model| {
+                model.transaction_type = "0x100".to_string()
+            }),
+            ("transaction_hash", |model| {
+                model.transaction_hash = vec![1; 31]
+            }),
+            ("transaction_nonce", |model| model.transaction_nonce = -1),
+            ("block_hash", |model| model.block_hash = Some(vec![1; 33])),
+            ("block_number", |model| model.block_number = -1),
+            ("transaction_index", |model| {
+                model.transaction_index = Some(-1)
+            }),
+            ("_from", |model| model._from = vec![1; 19]),
+            ("_to", |model| model._to = Some(Vec::new())),
+            ("value", |model| model.value = "-1".parse().unwrap()),
+            ("gas_price", |model| {
+                model.gas_price = Some("0.5".parse().unwrap())
+            }),
+            ("gas", |model| model.gas = -1),
+            ("access_list", |model| {
+                model.access_list = Some(serde_json::json!("none"))
+            }),
+            ("max_priority_fee_per_gas", |model| {
+                model.max_priority_fee_per_gas = Some("1e40".parse().unwrap())
+            }),
+            ("max_fee_per_gas", |model| {
+                model.max_fee_per_gas = Some("-5".parse().unwrap())
+            }),
+            ("max_fee_per_blob_gas", |model| {
+                model.max_fee_per_blob_gas = Some("1.5".parse().unwrap())
+            }),
+            ("blob_versioned_hashes", |model| {
+                model.blob_versioned_hashes = Some(serde_json::json!(["0x01"]))
+            }),
+            ("authorization_list", |model| {
+                model.authorization_list = Some(serde_json::json!([{ "address": 1 }]))
+            }),
+            ("raw_transaction", |model| {
+                model.raw_transaction = Some(vec![0x02, 0xc0])
+            }),
+        ];
+
+        for (field, corrupt) in cases {
+            let mut model = TxModel::from(1, eip4844(), &Tx::Native);
+            corrupt(&mut model);
+
+            assert_eq!(
+                AlloyTx::try_from(model).unwrap_err(),
+                ConversionError::InvalidField(field),
+                "{}",
+                field
+            );
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use alloy::hex;
+use deadpool::managed::{self, Metrics, RecycleResult};
+use diesel::{pg::CopyFormat, prelude::*, ConnectionError, PgConnection};
+use std::io::Write;
+
+use crate::{from_numeric, TxModel};
+
+diesel::table! {
+    /// Session-local table that bulk loads of `transactions` are copied into
+    /// before being merged.
+    transactions_staging (chain_id, block_number, transaction_hash) {
+        chain_id -> Int8,
+        transaction_hash -> Bytea,
+        transaction_nonce -> Int8,
+        block_hash -> Nullable<Bytea>,
+        block_number -> Int8,
+        transaction_index -> Nullable<Int8>,
+        _from -> Bytea,
+        _to -> Nullable<Bytea>,
+        value -> Numeric,
+        gas_price -> Nullable<Numeric>,
+        gas -> Int8,
+        input -> Bytea,
+        v -> Text,
+        r -> Text,
+        s -> Text,
+        transaction_type -> Text,
+        impersonated -> Bool,
+        max_priority_fee_per_gas -> Nullable<Numeric>,
+        max_fee_per_gas -> Nullable<Numeric>,
+        tx_type -> Text,
+        access_list -> Nullable<Jsonb>,
+        max_fee_per_blob_gas -> Nullable<Numeric>,
+        blob_versioned_hashes -> Nullable<Jsonb>,
+        authorization_list -> Nullable<Jsonb>,
+        y_parity -> Nullable<Bool>,
+        raw_transaction -> Nullable<Bytea>,
+    }
+}
+
+/// Opens the blocking connections bulk loads stream `COPY` through, as pooled
+/// async connections cannot stream one.
+pub(crate) struct CopyConnectionManager {
+    pub db_url: String,
+}
+
+impl managed::Manager for CopyConnectionManager {
+    type Type = PgConnection;
+    type Error = ConnectionError;
+
+    async fn create(&self) -> Result<PgConnection, ConnectionError> {
+        let db_url = self.db_url.clone();
+        tokio::task::spawn_blocking(move || PgConnection::establish(&db_url))
+            .await
+            .map_err(|e| ConnectionError::BadConnection(e.to_string()))?
+    }
+
+    /// Connections are taken out of the pool when a load fails, so the ones
+    /// handed back are usable.
+    async fn recycle(&self, _: &mut PgConnection, _: &Metrics) -> RecycleResult<ConnectionError> {
+        Ok(())
+    }
+}
+
+/// Copies `txns` into a staging table with `COPY`, then merges them into
+/// `transactions` in the same transaction, skipping the rows already stored.
+/// Returns the number of inserted rows. The partitions of the rows must exist.
+pub(crate) fn copy_txns(conn: &mut PgConnection, txns: &[TxModel]) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        diesel::sql_query(
+            "CREATE TEMP TABLE transactions_staging \
+                (LIKE transactions INCLUDING DEFAULTS) ON COMMIT DROP",
+        )
+        .execute(conn)?;
+
+        diesel::copy_from(transactions_staging::table)
+            .from_raw_data(transactions_staging::table, |copy| {
+                write_csv(copy, txns)
+                    .map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))
+            })
+            .with_format(CopyFormat::Csv)
+            .execute(conn)?;
+
+        diesel::sql_query(
+            "INSERT INTO transactions SELECT * FROM transactions_staging \
+            ON CONFLICT (chain_id, block_number, transaction_hash) DO NOTHING",
+        )
+        .execute(conn)
+    })
+}
+
+/// Writes `txns` as CSV rows in the column order of `transactions_staging`.
+/// Empty unquoted fields are NULL, so text is always quoted.
+fn write_csv(out: &mut dyn Write, txns: &[TxModel]) -> std::io::Result<()> {
+    for txn in txns {
+        let fields = [
+            Some(txn.chain_id.to_string()),
+            Some(bytea(&txn.transaction_hash)),
+            Some(txn.transaction_nonce.to_string()),
+            txn.block_hash.as_deref().map(bytea),
+            Some(txn.block_number.to_string()),
+            txn.transaction_index.map(|x| x.to_string()),
+            Some(bytea(&txn._from)),
+            txn._to.as_deref().map(bytea),
+            Some(from_numeric(&txn.value).to_string()),
+            txn.gas_price.as_ref().map(|x| from_numeric(x).to_string()),
+            Some(txn.gas.to_string()),
+            Some(bytea(&txn.input)),
+            Some(quoted(&txn.v)),
+            Some(quoted(&txn.r)),
+            Some(quoted(&txn.s)),
+            Some(quoted(&txn.transaction_type)),
+            Some(txn.impersonated.to_string()),
+            txn.max_priority_fee_per_gas
+                .as_ref()
+                .map(|x| from_numeric(x).to_string()),
+            txn.max_fee_per_gas
+                .as_ref()
+                .map(|x| from_numeric(x).to_string()),
+            Some(quoted(&txn.tx_type)),
+            txn.access_list.as_ref().map(|x| quoted(&x.to_string())),
+            txn.max_fee_per_blob_gas
+                .as_ref()
+                .map(|x| from_numeric(x).to_string()),
+            txn.blob_versioned_hashes
+                .as_ref()
+                .map(|x| quoted(&x.to_string())),
+            txn.authorization_list
+                .as_ref()
+                .map(|x| quoted(&x.to_string())),
+            txn.y_parity.map(|x| x.to_string()),
+            txn.raw_transaction.as_deref().map(bytea),
+        ];
+
+        let row: Vec<&str> = fields
+            .iter()
+            .map(|field| field.as_deref().unwrap_or(""))
+            .collect();
+        writeln!(out, "{}", row.join(","))?;
+    }
+
+    Ok(())
+}
+
+fn bytea(bytes: &[u8]) -> String {
+    format!("\\x{}", hex::encode(bytes))
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
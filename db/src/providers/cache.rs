@@ -1,336 +1,613 @@
+use alloy::primitives::U256;
+use async_trait::async_trait;
 use redis::RedisResult;
+use std::collections::BTreeMap;
 
-use crate::{unix_ms_to_ist, Stride, Tx, Type};
+use crate::{
+    cache_migration::migrate_cache, to_unix_ms, BlockCounts, BlockModel, BlockSample, CacheUsage,
+    MetricsStore, RedisConnection, Retention, Tx,
+};
 
-pub fn add_block(
-    chain_id: &u64,
-    timestamp: i64,
-    successful_xfers: u64,
-    total_xfers: u64,
-    total_native_transfers: u64,
-    total_x_chain_transfers: u64,
-    tx_count: u64,
-    height: u64,
-    conn: &mut redis::Connection,
+// Per-chain keys wrap the chain id in a hash tag, e.g. `chain:{7890}:index`,
+// so Redis Cluster keeps them in one slot and a chain's pipelines, scripts
+// and transactions never span nodes.
+
+/// Hash holding the counts and bridged value of a single block.
+pub fn block_key(chain_id: &u64, height: u64) -> String {
+    format!("chain:{{{}}}:block:{}", chain_id, height)
+}
+
+/// Sorted set of block heights scored by block timestamp in milliseconds.
+pub fn block_index_key(chain_id: &u64) -> String {
+    format!("chain:{{{}}}:index", chain_id)
+}
+
+/// Hash holding the summed fields of the trimmed blocks of a rollup bucket.
+pub fn rollup_key(chain_id: &u64, start_ms: i64) -> String {
+    format!("chain:{{{}}}:rollup:{}", chain_id, start_ms)
+}
+
+/// Sorted set of rollup bucket starts, scored by the start in milliseconds.
+pub fn rollup_index_key(chain_id: &u64) -> String {
+    format!("chain:{{{}}}:rollups", chain_id)
+}
+
+/// Height of the latest indexed block of a chain.
+pub fn latest_height_key(chain_id: &u64) -> String {
+    format!("chain:{{{}}}:height", chain_id)
+}
+
+/// Timestamp of the latest indexed block of a chain.
+pub fn latest_timestamp_key(chain_id: &u64) -> String {
+    format!("chain:{{{}}}:timestamp", chain_id)
+}
+
+pub const BRIDGED_IN_FIELD: &str = "bridged_in";
+
+/// Block hash field holding the value delivered from `source_chain_id`.
+pub fn bridged_from_field(source_chain_id: &u64) -> String {
+    format!("bridged_from:{}", source_chain_id)
+}
+
+/// Writes the counts of many blocks, e.g. during a backfill, with one atomic
+/// round-trip per chain.
+pub async fn add_blocks(
+    blocks: &[BlockCounts],
+    ttl_secs: i64,
+    conn: &mut RedisConnection,
 ) -> RedisResult<()> {
-    // analytics/volume keys
-    let successful_key = format!("chain:{}:successful", chain_id);
-    let total_key = format!("chain:{}:total", chain_id);
+    let mut pipes: BTreeMap<u64, redis::Pipeline> = BTreeMap::new();
+    for block in blocks {
+        queue_block(
+            pipes.entry(block.chain_id).or_insert_with(atomic_pipe),
+            block,
+            ttl_secs,
+        );
+    }
 
-    // TPS keys
-    let tps_key = format!("chain:{}:tps", chain_id);
-    let x_chain_tps_key = format!("chain:{}:xtps", chain_id);
-    let native_chain_tps_key = format!("chain:{}:ntps", chain_id);
-    let live_tps_key = format!("chain:{}:live_tps", chain_id);
+    query_chain_pipes(pipes, conn).await
+}
+
+/// Rewrites the keys of blocks from their Postgres records, with one atomic
+/// round-trip per chain.
+pub async fn restore_blocks(
+    blocks: &[BlockModel],
+    ttl_secs: i64,
+    conn: &mut RedisConnection,
+) -> RedisResult<()> {
+    let mut pipes: BTreeMap<u64, redis::Pipeline> = BTreeMap::new();
+    for block in blocks {
+        let counts = block.block_counts();
+        queue_block(
+            pipes.entry(counts.chain_id).or_insert_with(atomic_pipe),
+            &counts,
+            ttl_secs,
+        );
+    }
 
-    // blocks keys
-    let height_key = format!("chain:{}:height", chain_id);
-    let timestamp_key = format!("chain:{}:timestamp", chain_id);
+    query_chain_pipes(pipes, conn).await
+}
 
-    // tx keys
-    let total_native_txns_key = format!("chain:{}:total_native", chain_id);
-    let total_x_chain_txns_key = format!("chain:{}:total_x_chain", chain_id);
+fn atomic_pipe() -> redis::Pipeline {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe
+}
 
+/// Registers the chains, then runs the pipeline of each. A pipeline only
+/// touches the keys of its chain, which share a slot, so it stays atomic on
+/// Redis Cluster.
+async fn query_chain_pipes(
+    pipes: BTreeMap<u64, redis::Pipeline>,
+    conn: &mut RedisConnection,
+) -> RedisResult<()> {
+    if pipes.is_empty() {
+        return Ok(());
+    }
+
+    let chain_ids: Vec<u64> = pipes.keys().copied().collect();
     redis::cmd("SADD")
         .arg("chains")
-        .arg(chain_id.to_string())
-        .query::<()>(conn)?;
-
-    redis::cmd("ZADD")
-        .arg(&successful_key)
-        .arg(timestamp)
-        .arg(successful_xfers)
-        .query::<()>(conn)?;
-
-    redis::cmd("ZADD")
-        .arg(&total_key)
-        .arg(timestamp)
-        .arg(total_xfers)
-        .query::<()>(conn)?;
-
-    redis::cmd("ZADD")
-        .arg(&live_tps_key)
-        .arg(timestamp)
-        .arg(tx_count)
-        .query::<()>(conn)?;
-
-    redis::cmd("ZADD")
-        .arg(&total_native_txns_key)
-        .arg(timestamp)
-        .arg(total_native_transfers)
-        .query::<()>(conn)?;
-
-    redis::cmd("ZADD")
-        .arg(&total_x_chain_txns_key)
-        .arg(timestamp)
-        .arg(total_x_chain_transfers)
-        .query::<()>(conn)?;
-
-    redis::cmd("SET")
-        .arg(&tps_key)
-        .arg(tx_count)
-        .query::<()>(conn)?;
-
-    redis::cmd("SET")
-        .arg(&x_chain_tps_key)
-        .arg(total_x_chain_transfers)
-        .query::<()>(conn)?;
-
-    redis::cmd("SET")
-        .arg(&native_chain_tps_key)
-        .arg(total_native_transfers)
-        .query::<()>(conn)?;
-
-    redis::cmd("SET")
-        .arg(&height_key)
-        .arg(height)
-        .query::<()>(conn)?;
-
-    redis::cmd("SET")
-        .arg(&timestamp_key)
-        .arg(timestamp)
-        .query::<()>(conn)?;
+        .arg(chain_ids)
+        .query_async::<()>(conn)
+        .await?;
+
+    for pipe in pipes.values() {
+        pipe.query_async::<()>(conn).await?;
+    }
 
     Ok(())
 }
 
-pub fn get_latest_height(chain_id: &u64, conn: &mut redis::Connection) -> RedisResult<u64> {
-    let height_key = format!("chain:{}:height", chain_id);
-    let height = redis::cmd("GET").arg(&height_key).query::<u64>(conn)?;
+/// Queues the writes of a block: its hash and index entry, keyed by height so
+/// blocks never overwrite each other, and the latest keys of the chain.
+fn queue_block(pipe: &mut redis::Pipeline, block: &BlockCounts, ttl_secs: i64) {
+    let chain_id = &block.chain_id;
+    let timestamp_ms = to_unix_ms(block.timestamp);
+
+    pipe.cmd("HSET")
+        .arg(block_key(chain_id, block.height))
+        .arg("height")
+        .arg(block.height)
+        .arg("timestamp_ms")
+        .arg(timestamp_ms)
+        .arg(block.fields())
+        .ignore();
+
+    if !block.received_by_source.is_empty() {
+        queue_bridged_value(pipe, chain_id, block.height, &block.received_by_source);
+    }
+
+    // blocks are trimmed well before they expire; the TTL only lets
+    // `volatile-ttl` evict the oldest blocks first if memory runs out
+    pipe.cmd("EXPIRE")
+        .arg(block_key(chain_id, block.height))
+        .arg(ttl_secs)
+        .ignore();
+
+    pipe.cmd("ZADD")
+        .arg(block_index_key(chain_id))
+        .arg(timestamp_ms)
+        .arg(block.height)
+        .ignore();
+
+    let mut latest: Vec<(String, u64)> = vec![
+        (latest_height_key(chain_id), block.height),
+        (latest_timestamp_key(chain_id), block.timestamp as u64),
+        (Tx::All.tps_key(chain_id), block.tx_count),
+    ];
+    for category in Tx::CATEGORIES.iter() {
+        let counts = block
+            .category_counts
+            .get(category)
+            .copied()
+            .unwrap_or_default();
+        latest.push((category.tps_key(chain_id), counts.total));
+    }
+    let (keys, values): (Vec<String>, Vec<u64>) = latest.into_iter().unzip();
+
+    pipe.cmd("EVAL")
+        .arg(SET_LATEST_SCRIPT)
+        .arg(keys.len())
+        .arg(keys)
+        .arg(values)
+        .ignore();
+}
+
+/// Queues the value delivered in a block, in total and per source chain.
+fn queue_bridged_value(
+    pipe: &mut redis::Pipeline,
+    chain_id: &u64,
+    height: u64,
+    received_by_source: &BTreeMap<u64, U256>,
+) {
+    pipe.cmd("HSET")
+        .arg(block_key(chain_id, height))
+        .arg(bridged_fields(received_by_source))
+        .ignore();
+}
+
+/// Block fields of the value delivered in a block, in total and per source
+/// chain.
+pub fn bridged_fields(received_by_source: &BTreeMap<u64, U256>) -> Vec<(String, String)> {
+    let received = received_by_source
+        .values()
+        .fold(U256::ZERO, |total, amount| total.saturating_add(*amount));
+
+    let mut fields = vec![(BRIDGED_IN_FIELD.to_string(), received.to_string())];
+    fields.extend(received_by_source.iter().map(|(source_chain_id, amount)| {
+        (bridged_from_field(source_chain_id), amount.to_string())
+    }));
+
+    fields
+}
+
+pub async fn get_chains(conn: &mut RedisConnection) -> RedisResult<Vec<u64>> {
+    redis::cmd("SMEMBERS").arg("chains").query_async(conn).await
+}
+
+pub async fn get_latest_height(chain_id: &u64, conn: &mut RedisConnection) -> RedisResult<u64> {
+    let height = redis::cmd("GET")
+        .arg(latest_height_key(chain_id))
+        .query_async::<u64>(conn)
+        .await?;
 
     Ok(height)
 }
 
-pub fn get_latest_timestamp(chain_id: &u64, conn: &mut redis::Connection) -> RedisResult<i64> {
-    let timestamp_key = format!("chain:{}:timestamp", chain_id);
-    let timestamp = redis::cmd("GET").arg(&timestamp_key).query::<i64>(conn)?;
+pub async fn get_latest_timestamp(chain_id: &u64, conn: &mut RedisConnection) -> RedisResult<i64> {
+    let timestamp = redis::cmd("GET")
+        .arg(latest_timestamp_key(chain_id))
+        .query_async::<i64>(conn)
+        .await?;
 
     Ok(timestamp)
 }
 
-pub fn get_live_tps(
+/// Heights of the blocks of a chain with a timestamp in `(from_ms, to_ms]`,
+/// oldest first.
+pub async fn get_heights_in_range(
+    chain_id: &u64,
+    from_ms: i64,
+    to_ms: i64,
+    conn: &mut RedisConnection,
+) -> RedisResult<Vec<u64>> {
+    redis::cmd("ZRANGEBYSCORE")
+        .arg(block_index_key(chain_id))
+        .arg(format!("({}", from_ms))
+        .arg(to_ms)
+        .query_async(conn)
+        .await
+}
+
+async fn get_block_samples_at(
     chain_id: &u64,
-    stride: Stride,
-    tx_type: Type,
-    conn: &mut redis::Connection,
-) -> RedisResult<Vec<(u64, String)>> {
-    let latest_timestamp = get_latest_timestamp(chain_id, conn)?;
-
-    let mut stride = stride.stride.unwrap_or(1);
-    if stride == 1 {
-        stride = 3600;
-    } else {
-        stride = 600;
+    heights: &[u64],
+    conn: &mut RedisConnection,
+) -> RedisResult<Vec<BlockSample>> {
+    if heights.is_empty() {
+        return Ok(Vec::new());
     }
 
-    let raw = if let Some(Tx::CrossChain) = tx_type.tx_type {
-        let total_x_chain_txns_key = format!("chain:{}:total_x_chain", chain_id);
-        let raw: Vec<String> = redis::cmd("ZRANGEBYSCORE")
-            .arg(&total_x_chain_txns_key)
-            .arg(latest_timestamp.saturating_sub(stride as i64))
-            .arg(latest_timestamp)
-            .arg("WITHSCORES")
-            .query(conn)?;
-        raw
-    } else if let Some(Tx::Native) = tx_type.tx_type {
-        let total_x_chain_txns_key = format!("chain:{}:total_native", chain_id);
-        let raw: Vec<String> = redis::cmd("ZRANGEBYSCORE")
-            .arg(&total_x_chain_txns_key)
-            .arg(latest_timestamp.saturating_sub(stride as i64))
-            .arg(latest_timestamp)
-            .arg("WITHSCORES")
-            .query(conn)?;
-        raw
-    } else if let Some(Tx::All) = tx_type.tx_type {
-        let live_tps_key = format!("chain:{}:live_tps", chain_id);
-        let raw: Vec<String> = redis::cmd("ZRANGEBYSCORE")
-            .arg(&live_tps_key)
-            .arg(latest_timestamp.saturating_sub(stride as i64))
-            .arg(latest_timestamp)
-            .arg("WITHSCORES")
-            .query(conn)?;
-        raw
-    } else {
+    let mut pipe = redis::pipe();
+    for height in heights {
+        pipe.cmd("HGETALL").arg(block_key(chain_id, *height));
+    }
+    let blocks: Vec<BTreeMap<String, String>> = pipe.query_async(conn).await?;
+
+    Ok(blocks.iter().filter_map(BlockSample::from_fields).collect())
+}
+
+/// Values of a block hash field over the blocks of a chain with a timestamp
+/// in `(from_ms, to_ms]`, plus the rollups starting in that range. Blocks
+/// without the field are skipped.
+pub async fn get_block_field_in_range(
+    chain_id: &u64,
+    field: &str,
+    from_ms: i64,
+    to_ms: i64,
+    conn: &mut RedisConnection,
+) -> RedisResult<Vec<String>> {
+    let mut keys: Vec<String> = get_heights_in_range(chain_id, from_ms, to_ms, conn)
+        .await?
+        .into_iter()
+        .map(|height| block_key(chain_id, height))
+        .collect();
+    let rollup_starts: Vec<i64> = redis::cmd("ZRANGEBYSCORE")
+        .arg(rollup_index_key(chain_id))
+        .arg(format!("({}", from_ms))
+        .arg(to_ms)
+        .query_async(conn)
+        .await?;
+    keys.extend(
+        rollup_starts
+            .into_iter()
+            .map(|start_ms| rollup_key(chain_id, start_ms)),
+    );
+    if keys.is_empty() {
         return Ok(Vec::new());
-    };
+    }
 
-    let mut pairs: Vec<(String, f64)> = Vec::new();
-    for chunk in raw.chunks_exact(2) {
-        let member_str = chunk[0].clone();
-        let score_str = chunk[1].clone();
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("HGET").arg(key).arg(field);
+    }
+    let values: Vec<Option<String>> = pipe.query_async(conn).await?;
+
+    Ok(values.into_iter().flatten().collect())
+}
 
-        if let Ok(score_val) = score_str.parse::<f64>() {
-            pairs.push((member_str, score_val));
+/// Merges the blocks of a chain older than `retention.raw_secs` into rollup
+/// buckets, and drops rollups older than `retention.rollup_secs`. Returns the
+/// number of trimmed blocks.
+pub async fn trim_chain(
+    chain_id: &u64,
+    retention: &Retention,
+    now: i64,
+    conn: &mut RedisConnection,
+) -> RedisResult<usize> {
+    let raw_cutoff_ms = now.saturating_sub(retention.raw_secs) * 1000;
+    let rollup_cutoff_ms = now.saturating_sub(retention.rollup_secs) * 1000;
+    let bucket_ms = retention.rollup_bucket_secs.max(1) * 1000;
+    let mut trimmed = 0;
+
+    loop {
+        let heights: Vec<u64> = redis::cmd("ZRANGEBYSCORE")
+            .arg(block_index_key(chain_id))
+            .arg("-inf")
+            .arg(raw_cutoff_ms)
+            .arg("LIMIT")
+            .arg(0)
+            .arg(TRIM_BATCH_SIZE)
+            .query_async(conn)
+            .await?;
+        if heights.is_empty() {
+            break;
         }
-    }
 
-    let mut tps_pairs: Vec<(u64, String)> = Vec::new();
-    for (member_str, score) in &pairs {
-        let timestamp = *score as i64;
-        let ist_day = unix_ms_to_ist(timestamp);
-        if let Ok(value) = member_str.parse::<u64>() {
-            tps_pairs.push((value, ist_day));
+        let mut pipe = redis::pipe();
+        for height in &heights {
+            pipe.cmd("HGETALL").arg(block_key(chain_id, *height));
+        }
+        let blocks: Vec<BTreeMap<String, String>> = pipe.query_async(conn).await?;
+
+        let mut rollups: BTreeMap<i64, BTreeMap<String, U256>> = BTreeMap::new();
+        for fields in &blocks {
+            let timestamp_ms = match fields
+                .get("timestamp_ms")
+                .and_then(|ts| ts.parse::<i64>().ok())
+            {
+                Some(timestamp_ms) => timestamp_ms,
+                None => continue,
+            };
+            let start_ms = timestamp_ms - timestamp_ms.rem_euclid(bucket_ms);
+            add_fields(rollups.entry(start_ms).or_default(), fields);
         }
+
+        rollups.retain(|start_ms, _| *start_ms > rollup_cutoff_ms);
+
+        let mut pipe = redis::pipe();
+        for start_ms in rollups.keys() {
+            pipe.cmd("HGETALL").arg(rollup_key(chain_id, *start_ms));
+        }
+        let existing: Vec<BTreeMap<String, String>> = if rollups.is_empty() {
+            Vec::new()
+        } else {
+            pipe.query_async(conn).await?
+        };
+
+        // the merged rollups and the deletion of their blocks are applied in
+        // one transaction, so an interrupted trim never merges a block twice
+        let mut pipe = atomic_pipe();
+        for ((start_ms, mut merged), existing) in rollups.into_iter().zip(&existing) {
+            add_fields(&mut merged, existing);
+            let fields: Vec<(String, String)> = merged
+                .into_iter()
+                .map(|(field, value)| (field, value.to_string()))
+                .collect();
+
+            let key = rollup_key(chain_id, start_ms);
+            pipe.cmd("HSET").arg(&key).arg(fields).ignore();
+            pipe.cmd("EXPIRE")
+                .arg(&key)
+                .arg(retention.rollup_secs.saturating_mul(2))
+                .ignore();
+            pipe.cmd("ZADD")
+                .arg(rollup_index_key(chain_id))
+                .arg(start_ms)
+                .arg(start_ms)
+                .ignore();
+        }
+
+        let block_keys: Vec<String> = heights
+            .iter()
+            .map(|height| block_key(chain_id, *height))
+            .collect();
+        pipe.cmd("DEL").arg(block_keys).ignore();
+        pipe.cmd("ZREM")
+            .arg(block_index_key(chain_id))
+            .arg(&heights[..])
+            .ignore();
+        pipe.query_async::<()>(conn).await?;
+
+        trimmed += heights.len();
     }
 
-    tps_pairs.sort_by(|a, b| a.1.cmp(&b.1));
+    let expired: Vec<i64> = redis::cmd("ZRANGEBYSCORE")
+        .arg(rollup_index_key(chain_id))
+        .arg("-inf")
+        .arg(rollup_cutoff_ms)
+        .query_async(conn)
+        .await?;
+    if !expired.is_empty() {
+        let rollup_keys: Vec<String> = expired
+            .iter()
+            .map(|start_ms| rollup_key(chain_id, *start_ms))
+            .collect();
+        let mut pipe = atomic_pipe();
+        pipe.cmd("DEL").arg(rollup_keys).ignore();
+        pipe.cmd("ZREMRANGEBYSCORE")
+            .arg(rollup_index_key(chain_id))
+            .arg("-inf")
+            .arg(rollup_cutoff_ms)
+            .ignore();
+        pipe.query_async::<()>(conn).await?;
+    }
 
-    Ok(tps_pairs)
+    Ok(trimmed)
 }
 
-pub fn get_all_chains_live_tps_in_range(
-    stride: Stride,
-    tx_type: Type,
-    conn: &mut redis::Connection,
-) -> redis::RedisResult<Vec<(u64, String)>> {
-    let chain_ids: Vec<u64> = redis::cmd("SMEMBERS").arg("chains").query(conn)?;
-    let mut all_chains: Vec<Vec<(u64, String)>> = Vec::new();
-    let mut max_size = 0;
-    let mut longest_chain: Vec<(u64, String)> = Vec::new();
-
-    for chain_id in chain_ids {
-        match get_live_tps(&chain_id, stride.clone(), tx_type.clone(), conn) {
-            Ok(chain_live_tps) => {
-                if chain_live_tps.len() > max_size {
-                    max_size = chain_live_tps.len();
-                    longest_chain = chain_live_tps.clone();
-                }
-
-                all_chains.push(chain_live_tps);
-            }
-            Err(e) => {
-                return Err(e);
-            }
+/// Adds the count and value fields of a block or rollup hash to `sums`.
+pub(crate) fn add_fields(sums: &mut BTreeMap<String, U256>, fields: &BTreeMap<String, String>) {
+    for (field, value) in fields {
+        if field == "height" || field == "timestamp_ms" {
+            continue;
+        }
+        if let Ok(value) = value.parse::<U256>() {
+            let sum = sums.entry(field.clone()).or_insert(U256::ZERO);
+            *sum = sum.saturating_add(value);
         }
     }
+}
 
-    let mut final_chain_live_tps = vec![(0, String::new()); max_size];
+/// Estimates the memory held by the series of a chain from the indexes and
+/// the sizes of the latest blocks and rollups.
+pub async fn get_cache_usage(
+    chain_id: &u64,
+    conn: &mut RedisConnection,
+) -> RedisResult<CacheUsage> {
+    let mut usage = CacheUsage {
+        chain_id: *chain_id,
+        ..Default::default()
+    };
 
-    for chain in &all_chains {
-        let offset = max_size - chain.len();
-        for (i, &(val, _)) in chain.iter().enumerate() {
-            final_chain_live_tps[offset + i].0 += val;
+    for (index_key, is_rollup) in [
+        (block_index_key(chain_id), false),
+        (rollup_index_key(chain_id), true),
+    ] {
+        let count: u64 = redis::cmd("ZCARD")
+            .arg(&index_key)
+            .query_async(conn)
+            .await?;
+        let index_bytes: Option<u64> = redis::cmd("MEMORY")
+            .arg("USAGE")
+            .arg(&index_key)
+            .query_async(conn)
+            .await?;
+        let members: Vec<String> = redis::cmd("ZREVRANGE")
+            .arg(&index_key)
+            .arg(0)
+            .arg(USAGE_SAMPLES - 1)
+            .query_async(conn)
+            .await?;
+
+        let mut sampled_bytes = 0u64;
+        for member in &members {
+            let key = if is_rollup {
+                rollup_key(chain_id, member.parse().unwrap_or_default())
+            } else {
+                block_key(chain_id, member.parse().unwrap_or_default())
+            };
+            let bytes: Option<u64> = redis::cmd("MEMORY")
+                .arg("USAGE")
+                .arg(&key)
+                .query_async(conn)
+                .await?;
+            sampled_bytes += bytes.unwrap_or(0);
         }
-    }
+        let average_bytes = sampled_bytes / (members.len() as u64).max(1);
 
-    for i in 0..max_size {
-        if let Some((_, ref ts)) = longest_chain.get(i) {
-            final_chain_live_tps[i].1 = ts.clone();
+        usage.bytes += index_bytes.unwrap_or(0) + average_bytes * count;
+        if is_rollup {
+            usage.rollups = count;
+        } else {
+            usage.blocks = count;
         }
     }
 
-    Ok(final_chain_live_tps)
+    Ok(usage)
 }
 
-pub fn get_latest_tps(
+/// Last `window + 1` block samples of a chain, oldest first.
+pub async fn get_block_samples(
     chain_id: &u64,
-    tx_type: Type,
-    conn: &mut redis::Connection,
-) -> RedisResult<u64> {
-    let tps_key = if let Some(Tx::CrossChain) = tx_type.tx_type {
-        format!("chain:{}:xtps", chain_id)
-    } else if let Some(Tx::Native) = tx_type.tx_type {
-        format!("chain:{}:ntps", chain_id)
-    } else if let Some(Tx::All) = tx_type.tx_type {
-        format!("chain:{}:tps", chain_id)
-    } else {
-        return Ok(0);
-    };
+    window: u64,
+    conn: &mut RedisConnection,
+) -> RedisResult<Vec<BlockSample>> {
+    let mut heights: Vec<u64> = redis::cmd("ZREVRANGE")
+        .arg(block_index_key(chain_id))
+        .arg(0)
+        .arg(window)
+        .query_async(conn)
+        .await?;
+    heights.reverse();
+
+    get_block_samples_at(chain_id, &heights, conn).await
+}
 
-    let tps = redis::cmd("GET").arg(&tps_key).query::<u64>(conn)?;
+/// Block samples with a timestamp in `(from_ms, to_ms]`, oldest first.
+pub async fn get_block_samples_in_range(
+    chain_id: &u64,
+    from_ms: i64,
+    to_ms: i64,
+    conn: &mut RedisConnection,
+) -> RedisResult<Vec<BlockSample>> {
+    let heights = get_heights_in_range(chain_id, from_ms, to_ms, conn).await?;
 
-    Ok(tps)
+    get_block_samples_at(chain_id, &heights, conn).await
 }
 
-pub fn get_successful_xfers_in_range(
-    chain_id: &u64,
-    stride: i64,
-    latest_timestamp: i64,
-    tx_type: Type,
-    conn: &mut redis::Connection,
-) -> RedisResult<u64> {
-    let raw = if let Some(Tx::CrossChain) = tx_type.tx_type {
-        let total_x_chain_txns_key = format!("chain:{}:total_x_chain", chain_id);
-        let raw: Vec<String> = redis::cmd("ZRANGEBYSCORE")
-            .arg(&total_x_chain_txns_key)
-            .arg(latest_timestamp.saturating_sub(stride))
-            .arg(latest_timestamp)
-            .arg("WITHSCORES")
-            .query(conn)?;
-        raw
-    } else if let Some(Tx::Native) = tx_type.tx_type {
-        let total_x_chain_txns_key = format!("chain:{}:total_native", chain_id);
-        let raw: Vec<String> = redis::cmd("ZRANGEBYSCORE")
-            .arg(&total_x_chain_txns_key)
-            .arg(latest_timestamp.saturating_sub(stride))
-            .arg(latest_timestamp)
-            .arg("WITHSCORES")
-            .query(conn)?;
-        raw
-    } else if let Some(Tx::All) = tx_type.tx_type {
-        let successful_key = format!("chain:{}:successful", chain_id);
-        let raw: Vec<String> = redis::cmd("ZRANGEBYSCORE")
-            .arg(&successful_key)
-            .arg(latest_timestamp.saturating_sub(stride as i64))
-            .arg(latest_timestamp)
-            .arg("WITHSCORES")
-            .query(conn)?;
-        raw
-    } else {
-        return Ok(0);
-    };
+/// Metrics store backed by Redis, in the key layout above.
+#[derive(Clone)]
+pub struct RedisStore {
+    conn: RedisConnection,
+    retention: Retention,
+}
 
-    let mut pairs: Vec<(String, f64)> = Vec::new();
-    for chunk in raw.chunks_exact(2) {
-        let member_str = chunk[0].clone();
-        let score_str = chunk[1].clone();
+impl RedisStore {
+    /// Wraps a connection, bringing the key layout up to date first.
+    pub async fn new(conn: RedisConnection, retention: Retention) -> RedisResult<Self> {
+        migrate_cache(&mut conn.clone()).await?;
 
-        if let Ok(score_val) = score_str.parse::<f64>() {
-            pairs.push((member_str, score_val));
-        }
+        Ok(Self { conn, retention })
     }
 
-    let sum: u64 = pairs
-        .iter()
-        .map(|(member_str, _)| member_str.parse::<u64>().unwrap_or(0))
-        .sum();
-
-    Ok(sum)
+    /// Blocks are trimmed well before they expire, see `queue_block`.
+    fn block_ttl_secs(&self) -> i64 {
+        self.retention.raw_secs.saturating_mul(2)
+    }
 }
 
-pub fn get_all_chains_success_xfers_in_range(
-    stride: i64,
-    latest_timestamp: i64,
-    tx_type: Type,
-    conn: &mut redis::Connection,
-) -> redis::RedisResult<u64> {
-    let chain_ids: Vec<u64> = redis::cmd("SMEMBERS").arg("chains").query(conn)?;
-    let mut total_sum = 0u64;
-
-    for chain_id in chain_ids {
-        let chain_sum = get_successful_xfers_in_range(
-            &chain_id,
-            stride,
-            latest_timestamp,
-            tx_type.clone(),
-            conn,
-        )?;
-        total_sum += chain_sum;
+#[async_trait]
+impl MetricsStore for RedisStore {
+    async fn add_blocks(&self, blocks: &[BlockCounts]) -> RedisResult<()> {
+        add_blocks(blocks, self.block_ttl_secs(), &mut self.conn.clone()).await
     }
 
-    Ok(total_sum)
-}
+    async fn restore_blocks(&self, blocks: &[BlockModel]) -> RedisResult<()> {
+        restore_blocks(blocks, self.block_ttl_secs(), &mut self.conn.clone()).await
+    }
+
+    async fn get_chains(&self) -> RedisResult<Vec<u64>> {
+        get_chains(&mut self.conn.clone()).await
+    }
 
-pub fn get_all_chains_tps_in_range(
-    tx_type: Type,
-    conn: &mut redis::Connection,
-) -> redis::RedisResult<u64> {
-    let chain_ids: Vec<u64> = redis::cmd("SMEMBERS").arg("chains").query(conn)?;
-    let mut total_sum = 0u64;
-    for chain_id in chain_ids {
-        let chain_sum = get_latest_tps(&chain_id, tx_type.clone(), conn)?;
-        total_sum += chain_sum;
+    async fn get_latest_height(&self, chain_id: &u64) -> RedisResult<u64> {
+        get_latest_height(chain_id, &mut self.conn.clone()).await
     }
 
-    Ok(total_sum)
+    async fn get_latest_timestamp(&self, chain_id: &u64) -> RedisResult<i64> {
+        get_latest_timestamp(chain_id, &mut self.conn.clone()).await
+    }
+
+    async fn get_block_samples(
+        &self,
+        chain_id: &u64,
+        window: u64,
+    ) -> RedisResult<Vec<BlockSample>> {
+        get_block_samples(chain_id, window, &mut self.conn.clone()).await
+    }
+
+    async fn get_block_samples_in_range(
+        &self,
+        chain_id: &u64,
+        from_ms: i64,
+        to_ms: i64,
+    ) -> RedisResult<Vec<BlockSample>> {
+        get_block_samples_in_range(chain_id, from_ms, to_ms, &mut self.conn.clone()).await
+    }
+
+    async fn get_block_field_in_range(
+        &self,
+        chain_id: &u64,
+        field: &str,
+        from_ms: i64,
+        to_ms: i64,
+    ) -> RedisResult<Vec<String>> {
+        get_block_field_in_range(chain_id, field, from_ms, to_ms, &mut self.conn.clone()).await
+    }
+
+    async fn trim_chain(&self, chain_id: &u64, now: i64) -> RedisResult<usize> {
+        trim_chain(chain_id, &self.retention, now, &mut self.conn.clone()).await
+    }
+
+    async fn get_cache_usage(&self, chain_id: &u64) -> RedisResult<CacheUsage> {
+        get_cache_usage(chain_id, &mut self.conn.clone()).await
+    }
 }
+
+/// Sets `KEYS` to `ARGV` unless the chain already has a later block, so
+/// backfills and restores never move the latest keys backwards. `KEYS[1]`
+/// holds the latest height.
+const SET_LATEST_SCRIPT: &str = r#"
+local latest = tonumber(redis.call('GET', KEYS[1]))
+if latest and latest > tonumber(ARGV[1]) then
+    return 0
+end
+for i, key in ipairs(KEYS) do
+    redis.call('SET', key, ARGV[i])
+end
+return 1
+"#;
+const TRIM_BATCH_SIZE: usize = 1000;
+const USAGE_SAMPLES: i64 = 16;
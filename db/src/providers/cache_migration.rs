@@ -0,0 +1,298 @@
+use alloy::primitives::U256;
+use redis::{ErrorKind, RedisError, RedisResult};
+use std::collections::BTreeMap;
+
+use crate::{
+    block_index_key, block_key, bridged_from_field, to_unix_ms, BlockSample, RedisConnection, Tx,
+    BRIDGED_IN_FIELD,
+};
+
+const CACHE_VERSION_KEY: &str = "cache:version";
+const CACHE_VERSION: u64 = 2;
+const SCAN_COUNT: usize = 1000;
+
+/// Brings the Redis layout up to `CACHE_VERSION`.
+pub async fn migrate_cache(conn: &mut RedisConnection) -> RedisResult<()> {
+    let version: Option<u64> = redis::cmd("GET")
+        .arg(CACHE_VERSION_KEY)
+        .query_async(conn)
+        .await?;
+    let version = version.unwrap_or(0);
+    if version >= CACHE_VERSION {
+        return Ok(());
+    }
+
+    // the migrations rename keys across slots and only scan one node, so an
+    // older layout can't be migrated in place on Redis Cluster
+    if matches!(conn, RedisConnection::Cluster(_)) {
+        let chain_ids: Vec<u64> = redis::cmd("SMEMBERS")
+            .arg("chains")
+            .query_async(conn)
+            .await?;
+        if !chain_ids.is_empty() {
+            return Err(RedisError::from((
+                ErrorKind::ClientError,
+                "cannot migrate the cache on Redis Cluster",
+                format!(
+                    "found cache version {} but {} is required; migrate it on a standalone \
+                     or Sentinel deployment, or flush it and rebuild it from Postgres",
+                    version, CACHE_VERSION
+                ),
+            )));
+        }
+    }
+
+    if version < 1 {
+        migrate_v1(conn).await?;
+    }
+    if version < 2 {
+        migrate_v2(conn).await?;
+    }
+
+    redis::cmd("SET")
+        .arg(CACHE_VERSION_KEY)
+        .arg(CACHE_VERSION)
+        .query_async::<()>(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Version 0 kept one sorted set per counter with the count as member, so
+/// blocks with equal counts overwrote each other. Version 1 stores a hash
+/// per block and indexes heights by timestamp.
+///
+/// Blocks are rebuilt from `chain:{id}:blocks`, whose members were unique.
+/// Its totals are exact; successful counts and bridged values are only
+/// carried over where their timestamp maps to a single block, and blocks
+/// without a sample are dropped.
+async fn migrate_v1(conn: &mut RedisConnection) -> RedisResult<()> {
+    let chain_ids: Vec<u64> = redis::cmd("SMEMBERS")
+        .arg("chains")
+        .query_async(conn)
+        .await?;
+
+    for chain_id in &chain_ids {
+        migrate_chain_v1(chain_id, conn).await?;
+    }
+
+    // bridged_out is attributed to destination blocks, so every chain must be
+    // migrated first
+    for chain_id in &chain_ids {
+        let bridged_out_key = format!("chain:{}:bridged_out", chain_id);
+        for (timestamp_ms, amounts) in legacy_members_by_timestamp(&bridged_out_key, conn).await? {
+            let mut destinations = Vec::new();
+            for destination_chain_id in &chain_ids {
+                let heights: Vec<u64> = redis::cmd("ZRANGEBYSCORE")
+                    .arg(block_index_key(destination_chain_id))
+                    .arg(timestamp_ms)
+                    .arg(timestamp_ms)
+                    .query_async(conn)
+                    .await?;
+                for height in heights {
+                    let has_bridged_in: bool = redis::cmd("HEXISTS")
+                        .arg(block_key(destination_chain_id, height))
+                        .arg(BRIDGED_IN_FIELD)
+                        .query_async(conn)
+                        .await?;
+                    if has_bridged_in {
+                        destinations.push(block_key(destination_chain_id, height));
+                    }
+                }
+            }
+
+            if let [destination] = destinations.as_slice() {
+                redis::cmd("HSET")
+                    .arg(destination)
+                    .arg(bridged_from_field(chain_id))
+                    .arg(sum_members(&amounts))
+                    .query_async::<()>(conn)
+                    .await?;
+            }
+        }
+
+        redis::cmd("DEL")
+            .arg(&bridged_out_key)
+            .query_async::<()>(conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn migrate_chain_v1(chain_id: &u64, conn: &mut RedisConnection) -> RedisResult<()> {
+    let blocks_key = format!("chain:{}:blocks", chain_id);
+    let raw: Vec<String> = redis::cmd("ZRANGE")
+        .arg(&blocks_key)
+        .arg(0)
+        .arg(-1)
+        .query_async(conn)
+        .await?;
+    let samples: Vec<BlockSample> = raw
+        .iter()
+        .filter_map(|member_str| serde_json::from_str(member_str).ok())
+        .collect();
+
+    let mut heights_by_timestamp: BTreeMap<i64, Vec<u64>> = BTreeMap::new();
+    for sample in &samples {
+        heights_by_timestamp
+            .entry(sample.timestamp_ms)
+            .or_default()
+            .push(sample.height);
+
+        // without better data every transfer counts as successful
+        let mut fields: Vec<(String, u64)> = Vec::new();
+        for (category, count) in &sample.counts {
+            fields.push((format!("total:{}", category), *count));
+            fields.push((format!("successful:{}", category), *count));
+        }
+
+        redis::cmd("HSET")
+            .arg(block_key(chain_id, sample.height))
+            .arg("height")
+            .arg(sample.height)
+            .arg("timestamp_ms")
+            .arg(sample.timestamp_ms)
+            .arg(fields)
+            .query_async::<()>(conn)
+            .await?;
+
+        redis::cmd("ZADD")
+            .arg(block_index_key(chain_id))
+            .arg(sample.timestamp_ms)
+            .arg(sample.height)
+            .query_async::<()>(conn)
+            .await?;
+    }
+
+    let mut legacy_fields: Vec<(String, String)> = vec![
+        (
+            format!("chain:{}:successful", chain_id),
+            Tx::All.successful_field(),
+        ),
+        (
+            format!("chain:{}:bridged_in", chain_id),
+            BRIDGED_IN_FIELD.to_string(),
+        ),
+    ];
+    for category in Tx::CATEGORIES.iter() {
+        legacy_fields.push((
+            format!("chain:{}:successful_{}", chain_id, category),
+            category.successful_field(),
+        ));
+    }
+
+    for (legacy_key, field) in &legacy_fields {
+        for (timestamp_ms, members) in legacy_members_by_timestamp(legacy_key, conn).await? {
+            if let Some([height]) = heights_by_timestamp.get(&timestamp_ms).map(Vec::as_slice) {
+                redis::cmd("HSET")
+                    .arg(block_key(chain_id, *height))
+                    .arg(field)
+                    .arg(sum_members(&members))
+                    .query_async::<()>(conn)
+                    .await?;
+            }
+        }
+    }
+
+    let mut legacy_keys: Vec<String> = legacy_fields.into_iter().map(|(key, _)| key).collect();
+    legacy_keys.push(blocks_key);
+    legacy_keys.push(format!("chain:{}:total", chain_id));
+    legacy_keys.push(format!("chain:{}:live_tps", chain_id));
+    legacy_keys.push(format!("chain:{}:total_native", chain_id));
+    legacy_keys.push(format!("chain:{}:total_x_chain", chain_id));
+    for category in Tx::CATEGORIES.iter() {
+        legacy_keys.push(format!("chain:{}:total_{}", chain_id, category));
+    }
+
+    redis::cmd("DEL")
+        .arg(legacy_keys)
+        .query_async::<()>(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Version 2 wraps the chain id of per-chain keys in a hash tag, so Redis
+/// Cluster keeps the keys of a chain in one slot. Keys are renamed in place
+/// and keep their TTL.
+async fn migrate_v2(conn: &mut RedisConnection) -> RedisResult<()> {
+    let chain_ids: Vec<u64> = redis::cmd("SMEMBERS")
+        .arg("chains")
+        .query_async(conn)
+        .await?;
+
+    for chain_id in chain_ids {
+        let prefix = format!("chain:{}:", chain_id);
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(format!("{}*", prefix))
+                .arg("COUNT")
+                .arg(SCAN_COUNT)
+                .query_async(conn)
+                .await?;
+            keys.extend(batch);
+            if next_cursor == 0 {
+                break;
+            }
+            cursor = next_cursor;
+        }
+
+        for key in keys {
+            let tagged_key = format!("chain:{{{}}}:{}", chain_id, &key[prefix.len()..]);
+            let renamed = redis::cmd("RENAME")
+                .arg(&key)
+                .arg(&tagged_key)
+                .query_async::<()>(conn)
+                .await;
+            if let Err(e) = renamed {
+                // the key may have expired since the scan
+                let exists: bool = redis::cmd("EXISTS").arg(&key).query_async(conn).await?;
+                if exists {
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Members of a legacy sorted set grouped by their timestamp score, in
+/// milliseconds.
+async fn legacy_members_by_timestamp(
+    key: &str,
+    conn: &mut RedisConnection,
+) -> RedisResult<BTreeMap<i64, Vec<String>>> {
+    let raw: Vec<String> = redis::cmd("ZRANGE")
+        .arg(key)
+        .arg(0)
+        .arg(-1)
+        .arg("WITHSCORES")
+        .query_async(conn)
+        .await?;
+
+    let mut members: BTreeMap<i64, Vec<String>> = BTreeMap::new();
+    for chunk in raw.chunks_exact(2) {
+        if let Ok(score) = chunk[1].parse::<f64>() {
+            members
+                .entry(to_unix_ms(score as i64))
+                .or_default()
+                .push(chunk[0].clone());
+        }
+    }
+
+    Ok(members)
+}
+
+fn sum_members(members: &[String]) -> String {
+    members
+        .iter()
+        .filter_map(|member| member.parse::<U256>().ok())
+        .fold(U256::ZERO, |total, amount| total.saturating_add(amount))
+        .to_string()
+}
@@ -0,0 +1,282 @@
+use async_trait::async_trait;
+use redis::RedisResult;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound::{Excluded, Included},
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    add_fields, bridged_fields, to_unix_ms, BlockCounts, BlockModel, BlockSample, CacheUsage,
+    MetricsStore, Retention,
+};
+
+/// In-process metrics store with the same semantics as `RedisStore`, for
+/// small deployments and tests. The series are lost on restart.
+pub struct MemoryStore {
+    retention: Retention,
+    chains: RwLock<BTreeMap<u64, ChainSeries>>,
+}
+
+/// Mirror of the Redis keys of a chain.
+#[derive(Default)]
+struct ChainSeries {
+    /// Latest height and timestamp, set once the chain has a block.
+    latest: Option<(u64, i64)>,
+    /// Block fields by height.
+    blocks: BTreeMap<u64, BTreeMap<String, String>>,
+    /// `(timestamp_ms, height)` of the blocks.
+    index: BTreeSet<(i64, u64)>,
+    /// Summed fields of the trimmed blocks by bucket start in milliseconds.
+    rollups: BTreeMap<i64, BTreeMap<String, String>>,
+}
+
+impl ChainSeries {
+    fn add_block(&mut self, block: &BlockCounts) {
+        let timestamp_ms = to_unix_ms(block.timestamp);
+        let fields = self.blocks.entry(block.height).or_default();
+        if let Some(previous_ms) = fields
+            .get("timestamp_ms")
+            .and_then(|ts| ts.parse::<i64>().ok())
+        {
+            self.index.remove(&(previous_ms, block.height));
+        }
+
+        fields.insert("height".to_string(), block.height.to_string());
+        fields.insert("timestamp_ms".to_string(), timestamp_ms.to_string());
+        for (field, value) in block.fields() {
+            fields.insert(field, value.to_string());
+        }
+        if !block.received_by_source.is_empty() {
+            fields.extend(bridged_fields(&block.received_by_source));
+        }
+        self.index.insert((timestamp_ms, block.height));
+
+        if self
+            .latest
+            .is_none_or(|(latest_height, _)| latest_height <= block.height)
+        {
+            self.latest = Some((block.height, block.timestamp));
+        }
+    }
+
+    fn heights_in_range(&self, from_ms: i64, to_ms: i64) -> Vec<u64> {
+        self.index
+            .range((Excluded((from_ms, u64::MAX)), Included((to_ms, u64::MAX))))
+            .map(|(_, height)| *height)
+            .collect()
+    }
+
+    fn samples_at(&self, heights: &[u64]) -> Vec<BlockSample> {
+        heights
+            .iter()
+            .filter_map(|height| self.blocks.get(height))
+            .filter_map(BlockSample::from_fields)
+            .collect()
+    }
+}
+
+impl MemoryStore {
+    pub fn new(retention: Retention) -> Self {
+        Self {
+            retention,
+            chains: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl MetricsStore for MemoryStore {
+    async fn add_blocks(&self, blocks: &[BlockCounts]) -> RedisResult<()> {
+        let mut chains = self.chains.write().await;
+        for block in blocks {
+            chains.entry(block.chain_id).or_default().add_block(block);
+        }
+
+        Ok(())
+    }
+
+    async fn restore_blocks(&self, blocks: &[BlockModel]) -> RedisResult<()> {
+        let mut chains = self.chains.write().await;
+        for block in blocks {
+            let counts = block.block_counts();
+            chains
+                .entry(counts.chain_id)
+                .or_default()
+                .add_block(&counts);
+        }
+
+        Ok(())
+    }
+
+    async fn get_chains(&self) -> RedisResult<Vec<u64>> {
+        Ok(self
+            .chains
+            .read()
+            .await
+            .iter()
+            .filter(|(_, series)| series.latest.is_some())
+            .map(|(chain_id, _)| *chain_id)
+            .collect())
+    }
+
+    async fn get_latest_height(&self, chain_id: &u64) -> RedisResult<u64> {
+        self.chains
+            .read()
+            .await
+            .get(chain_id)
+            .and_then(|series| series.latest)
+            .map(|(height, _)| height)
+            .ok_or_else(|| (redis::ErrorKind::TypeError, "no indexed blocks").into())
+    }
+
+    async fn get_latest_timestamp(&self, chain_id: &u64) -> RedisResult<i64> {
+        self.chains
+            .read()
+            .await
+            .get(chain_id)
+            .and_then(|series| series.latest)
+            .map(|(_, timestamp)| timestamp)
+            .ok_or_else(|| (redis::ErrorKind::TypeError, "no indexed blocks").into())
+    }
+
+    async fn get_block_samples(
+        &self,
+        chain_id: &u64,
+        window: u64,
+    ) -> RedisResult<Vec<BlockSample>> {
+        let chains = self.chains.read().await;
+        let series = match chains.get(chain_id) {
+            Some(series) => series,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut heights: Vec<u64> = series
+            .index
+            .iter()
+            .rev()
+            .take(window.saturating_add(1) as usize)
+            .map(|(_, height)| *height)
+            .collect();
+        heights.reverse();
+
+        Ok(series.samples_at(&heights))
+    }
+
+    async fn get_block_samples_in_range(
+        &self,
+        chain_id: &u64,
+        from_ms: i64,
+        to_ms: i64,
+    ) -> RedisResult<Vec<BlockSample>> {
+        let chains = self.chains.read().await;
+
+        Ok(chains
+            .get(chain_id)
+            .map(|series| series.samples_at(&series.heights_in_range(from_ms, to_ms)))
+            .unwrap_or_default())
+    }
+
+    async fn get_block_field_in_range(
+        &self,
+        chain_id: &u64,
+        field: &str,
+        from_ms: i64,
+        to_ms: i64,
+    ) -> RedisResult<Vec<String>> {
+        let chains = self.chains.read().await;
+        let series = match chains.get(chain_id) {
+            Some(series) => series,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut values: Vec<String> = series
+            .heights_in_range(from_ms, to_ms)
+            .iter()
+            .filter_map(|height| series.blocks.get(height))
+            .filter_map(|fields| fields.get(field).cloned())
+            .collect();
+        values.extend(
+            series
+                .rollups
+                .range((Excluded(from_ms), Included(to_ms)))
+                .filter_map(|(_, fields)| fields.get(field).cloned()),
+        );
+
+        Ok(values)
+    }
+
+    async fn trim_chain(&self, chain_id: &u64, now: i64) -> RedisResult<usize> {
+        let raw_cutoff_ms = now.saturating_sub(self.retention.raw_secs) * 1000;
+        let rollup_cutoff_ms = now.saturating_sub(self.retention.rollup_secs) * 1000;
+        let bucket_ms = self.retention.rollup_bucket_secs.max(1) * 1000;
+
+        let mut chains = self.chains.write().await;
+        let series = match chains.get_mut(chain_id) {
+            Some(series) => series,
+            None => return Ok(0),
+        };
+
+        let trimmed: Vec<(i64, u64)> = series
+            .index
+            .range(..=(raw_cutoff_ms, u64::MAX))
+            .copied()
+            .collect();
+        for (timestamp_ms, height) in &trimmed {
+            series.index.remove(&(*timestamp_ms, *height));
+            let fields = match series.blocks.remove(height) {
+                Some(fields) => fields,
+                None => continue,
+            };
+
+            let start_ms = timestamp_ms - timestamp_ms.rem_euclid(bucket_ms);
+            if start_ms <= rollup_cutoff_ms {
+                continue;
+            }
+
+            let rollup = series.rollups.entry(start_ms).or_default();
+            let mut sums = BTreeMap::new();
+            add_fields(&mut sums, rollup);
+            add_fields(&mut sums, &fields);
+            *rollup = sums
+                .into_iter()
+                .map(|(field, value)| (field, value.to_string()))
+                .collect();
+        }
+
+        // Redis expires the hashes of blocks that were never indexed
+        if let Some((_, last_height)) = trimmed.last() {
+            series.blocks.retain(|height, fields| {
+                height > last_height || fields.contains_key("timestamp_ms")
+            });
+        }
+        series
+            .rollups
+            .retain(|start_ms, _| *start_ms > rollup_cutoff_ms);
+
+        Ok(trimmed.len())
+    }
+
+    /// `bytes` is the size of the stored fields and values.
+    async fn get_cache_usage(&self, chain_id: &u64) -> RedisResult<CacheUsage> {
+        let chains = self.chains.read().await;
+        let mut usage = CacheUsage {
+            chain_id: *chain_id,
+            ..Default::default()
+        };
+
+        if let Some(series) = chains.get(chain_id) {
+            usage.blocks = series.index.len() as u64;
+            usage.rollups = series.rollups.len() as u64;
+            usage.bytes = series
+                .blocks
+                .values()
+                .chain(series.rollups.values())
+                .flat_map(|fields| fields.iter())
+                .map(|(field, value)| (field.len() + value.len()) as u64)
+                .sum();
+        }
+
+        Ok(usage)
+    }
+}
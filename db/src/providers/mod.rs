@@ -1,4 +1,18 @@
+mod bulk;
 pub mod cache;
+pub mod cache_migration;
+pub mod memory;
+pub mod postgres;
 pub mod provider;
+pub mod queue;
+pub mod sqlite;
+pub mod storage;
+pub mod store;
 
 pub use cache::*;
+pub use memory::*;
+pub use postgres::*;
+pub use queue::*;
+pub use sqlite::*;
+pub use storage::*;
+pub use store::*;
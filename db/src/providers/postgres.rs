@@ -0,0 +1,841 @@
+use alloy::hex;
+use async_trait::async_trait;
+use deadpool::managed;
+use diesel::{
+    prelude::*,
+    sql_types::{Array, BigInt, Nullable, Text},
+    upsert::excluded,
+};
+use diesel_async::{
+    pooled_connection::deadpool::Pool, scoped_futures::ScopedFutureExt, AsyncConnection,
+    AsyncPgConnection, RunQueryDsl,
+};
+use futures::future::join_all;
+use std::{
+    collections::BTreeSet,
+    env,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::task;
+use tracing::{error, info};
+
+use crate::{
+    providers::bulk,
+    schema::{
+        blocks::dsl::{self as blocks_schema_types, blocks as blocks_schema},
+        bridge_messages::dsl::{
+            self as bridge_messages_schema_types, bridge_messages as bridge_messages_schema,
+        },
+        chains::dsl::chains as chains_schema,
+        dead_letters::dsl::dead_letters as dead_letters_schema,
+        transactions::dsl::{
+            self as transactions_schema_types, transactions as transactions_schema,
+        },
+    },
+    BlockModel, BridgeDeliveryModel, BridgeSendModel, Chain, DeadLetterModel, DeliveryLatency,
+    FailedBlockModel, RollupVolume, Storage, TimeWindow, Tx, TxFilter, TxIdentifier, TxModel,
+    ROLLUP_RESOLUTIONS,
+};
+
+/// Production storage backend on a pool of Postgres connections.
+#[derive(Clone)]
+pub struct PostgresStorage {
+    pool: Pool<AsyncPgConnection>,
+    /// Blocking connections for `COPY`, opened on first use.
+    copy_pool: managed::Pool<bulk::CopyConnectionManager>,
+    /// `(chain_id, first_block)` of the transaction partitions known to exist.
+    partitions: Arc<Mutex<BTreeSet<(i64, i64)>>>,
+}
+
+impl PostgresStorage {
+    pub fn new(pool: Pool<AsyncPgConnection>) -> Self {
+        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        let copy_pool = managed::Pool::builder(bulk::CopyConnectionManager { db_url })
+            .max_size(COPY_POOL_SIZE)
+            .build()
+            .expect("Failed to build copy pool");
+
+        Self {
+            pool,
+            copy_pool,
+            partitions: Arc::new(Mutex::new(BTreeSet::new())),
+        }
+    }
+
+    /// Creates the partitions of the chains and block ranges of `txns` that
+    /// this process has not seen yet.
+    async fn ensure_partitions(
+        &self,
+        conn: &mut AsyncPgConnection,
+        txns: &[TxModel],
+    ) -> Result<(), std::io::Error> {
+        let missing: BTreeSet<(i64, i64)> = {
+            let partitions = self.partitions.lock().unwrap();
+            txns.iter()
+                .map(|txn| {
+                    let first_block = txn.block_number
+                        - txn.block_number.rem_euclid(TRANSACTION_PARTITION_BLOCKS);
+                    (txn.chain_id, first_block)
+                })
+                .filter(|range| !partitions.contains(range))
+                .collect()
+        };
+
+        for (chain_id, first_block) in missing {
+            diesel::sql_query("SELECT ensure_transactions_partition($1, $2, $3)")
+                .bind::<BigInt, _>(chain_id)
+                .bind::<BigInt, _>(first_block)
+                .bind::<BigInt, _>(TRANSACTION_PARTITION_BLOCKS)
+                .execute(conn)
+                .await
+                .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+            self.partitions
+                .lock()
+                .unwrap()
+                .insert((chain_id, first_block));
+        }
+
+        Ok(())
+    }
+
+    /// Bulk loads `txns` with `COPY` on a connection of the copy pool, and
+    /// logs the throughput.
+    async fn copy_txns(&self, chain_id: i64, txns: Vec<TxModel>) -> Result<(), std::io::Error> {
+        let mut conn = self
+            .copy_pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+        let started = Instant::now();
+        let rows = txns.len();
+
+        let inserted = task::spawn_blocking(move || {
+            let inserted = bulk::copy_txns(&mut conn, &txns);
+            if inserted.is_err() {
+                // the connection may be broken, so it is not handed back
+                let _ = managed::Object::take(conn);
+            }
+            inserted.map_err(|_| std::io::ErrorKind::ConnectionAborted)
+        })
+        .await??;
+
+        let secs = started.elapsed().as_secs_f64();
+        info!(
+            "Copied {} transactions of chain {} ({} new) in {:.2}s, {:.0} rows/s",
+            rows,
+            chain_id,
+            inserted,
+            secs,
+            rows as f64 / secs.max(f64::EPSILON)
+        );
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn get_txs(
+        &self,
+        identifier: &TxIdentifier,
+        filter: &TxFilter,
+        tx_type: Option<Tx>,
+        limit: u64,
+    ) -> Result<Vec<TxModel>, std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        let mut query = transactions_schema.into_boxed();
+        query = query.order(transactions_schema_types::block_number.desc());
+        if let Some(chain_id) = filter.chain_id.as_ref() {
+            query = query.filter(transactions_schema_types::chain_id.eq(*chain_id as i64));
+        }
+        if let Some(tx_hash) = identifier.tx_hash.as_ref() {
+            let tx_hash = hex::decode(tx_hash).map_err(|_| std::io::ErrorKind::InvalidInput)?;
+            query = query.filter(transactions_schema_types::transaction_hash.eq(tx_hash));
+        }
+        if let Some(tpe) = tx_type.as_ref() {
+            match tpe {
+                Tx::All => {}
+                _ => {
+                    query = query.filter(transactions_schema_types::tx_type.eq(tpe.to_string()));
+                }
+            };
+        }
+
+        let result: Vec<TxModel> = query
+            .limit(limit as i64)
+            .offset((identifier.page_idx.unwrap_or(0) * limit) as i64)
+            .select(TxModel::as_select())
+            .load(&mut conn)
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(result)
+    }
+
+    async fn add_txns(&self, chain: Chain, txns: Vec<TxModel>) -> Result<(), std::io::Error> {
+        {
+            let mut conn = self
+                .pool
+                .get()
+                .await
+                .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+            diesel::insert_into(chains_schema)
+                .values(&chain)
+                .on_conflict(crate::schema::chains::chain_id)
+                .do_update()
+                .set(crate::schema::chains::latest_tps.eq(chain.latest_tps))
+                .execute(&mut conn)
+                .await
+                .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+            self.ensure_partitions(&mut conn, &txns).await?;
+        }
+
+        if txns.len() >= COPY_THRESHOLD {
+            return self.copy_txns(chain.chain_id, txns).await;
+        }
+
+        let mut tasks = Vec::new();
+        for chunk in txns.chunks(250) {
+            let chunk = chunk.to_vec();
+            let db_pool = self.pool.clone();
+            let task = task::spawn(async move {
+                let mut conn = db_pool
+                    .get()
+                    .await
+                    .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+                diesel::insert_into(transactions_schema)
+                    .values(&chunk)
+                    .on_conflict((
+                        crate::schema::transactions::chain_id,
+                        crate::schema::transactions::block_number,
+                        crate::schema::transactions::transaction_hash,
+                    ))
+                    .do_nothing()
+                    .execute(&mut conn)
+                    .await
+                    .map_err(std::io::Error::other)?;
+
+                Ok::<(), std::io::Error>(())
+            });
+
+            tasks.push(task);
+        }
+
+        // every chunk runs to the end, then the first failure fails the write
+        // so that the queue retries it
+        let mut result = Ok(());
+        for res in join_all(tasks).await {
+            let res = res.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            if let Err(e) = &res {
+                error!(
+                    "Failed to insert transactions of chain {}: {}",
+                    chain.chain_id, e
+                );
+            }
+            result = result.and(res);
+        }
+
+        result
+    }
+
+    async fn add_bridge_events(
+        &self,
+        sends: Vec<BridgeSendModel>,
+        deliveries: Vec<BridgeDeliveryModel>,
+    ) -> Result<(), std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        for chunk in sends.chunks(250) {
+            diesel::insert_into(bridge_messages_schema)
+                .values(chunk)
+                .on_conflict((
+                    bridge_messages_schema_types::source_chain_id,
+                    bridge_messages_schema_types::destination_chain_id,
+                    bridge_messages_schema_types::message_id,
+                ))
+                .do_update()
+                .set((
+                    bridge_messages_schema_types::sent_block
+                        .eq(excluded(bridge_messages_schema_types::sent_block)),
+                    bridge_messages_schema_types::sent_at
+                        .eq(excluded(bridge_messages_schema_types::sent_at)),
+                ))
+                .execute(&mut conn)
+                .await
+                .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+        }
+
+        for chunk in deliveries.chunks(250) {
+            diesel::insert_into(bridge_messages_schema)
+                .values(chunk)
+                .on_conflict((
+                    bridge_messages_schema_types::source_chain_id,
+                    bridge_messages_schema_types::destination_chain_id,
+                    bridge_messages_schema_types::message_id,
+                ))
+                .do_update()
+                .set((
+                    bridge_messages_schema_types::delivered_block
+                        .eq(excluded(bridge_messages_schema_types::delivered_block)),
+                    bridge_messages_schema_types::delivered_at
+                        .eq(excluded(bridge_messages_schema_types::delivered_at)),
+                ))
+                .execute(&mut conn)
+                .await
+                .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+        }
+
+        Ok(())
+    }
+
+    async fn match_bridge_messages(&self) -> Result<usize, std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        let matched = diesel::update(
+            bridge_messages_schema
+                .filter(bridge_messages_schema_types::latency_secs.is_null())
+                .filter(bridge_messages_schema_types::sent_at.is_not_null())
+                .filter(bridge_messages_schema_types::delivered_at.is_not_null()),
+        )
+        .set(
+            bridge_messages_schema_types::latency_secs
+                .eq(bridge_messages_schema_types::delivered_at
+                    - bridge_messages_schema_types::sent_at),
+        )
+        .execute(&mut conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(matched)
+    }
+
+    async fn add_block_record(&self, block: BlockModel) -> Result<(), std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                // a concurrent write of the same block waits on the insert, then
+                // finds the committed record below
+                let inserted = diesel::insert_into(blocks_schema)
+                    .values(&block)
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .await?;
+
+                if inserted == 0 {
+                    let previous = blocks_schema
+                        .filter(blocks_schema_types::chain_id.eq(block.chain_id))
+                        .filter(blocks_schema_types::block_number.eq(block.block_number))
+                        .select(BlockModel::as_select())
+                        .for_update()
+                        .first(conn)
+                        .await?;
+                    if previous == block {
+                        return Ok(());
+                    }
+
+                    diesel::update(blocks_schema)
+                        .filter(blocks_schema_types::chain_id.eq(block.chain_id))
+                        .filter(blocks_schema_types::block_number.eq(block.block_number))
+                        .set((
+                            blocks_schema_types::block_hash.eq(&block.block_hash),
+                            blocks_schema_types::block_timestamp.eq(block.block_timestamp),
+                            blocks_schema_types::tx_count.eq(block.tx_count),
+                            blocks_schema_types::counts.eq(&block.counts),
+                            blocks_schema_types::bridged.eq(&block.bridged),
+                        ))
+                        .execute(conn)
+                        .await?;
+                    add_rollups(conn, &previous, -1).await?;
+                }
+
+                add_rollups(conn, &block, 1).await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn get_block_records(
+        &self,
+        chain_id: Option<u64>,
+        from: i64,
+        to: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<BlockModel>, std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        let mut query = blocks_schema
+            .select(BlockModel::as_select())
+            .filter(blocks_schema_types::block_timestamp.gt(from))
+            .filter(blocks_schema_types::block_timestamp.le(to))
+            .into_boxed();
+        if let Some(chain_id) = chain_id {
+            query = query.filter(blocks_schema_types::chain_id.eq(chain_id as i64));
+        }
+
+        query
+            .order((
+                blocks_schema_types::chain_id.asc(),
+                blocks_schema_types::block_number.asc(),
+            ))
+            .limit(limit)
+            .offset(offset)
+            .load(&mut conn)
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted.into())
+    }
+
+    async fn rollup_volume(
+        &self,
+        chain_id: Option<u64>,
+        tx_type: Option<Tx>,
+        resolution: i64,
+        window: &TimeWindow,
+    ) -> Result<Vec<RollupVolume>, std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        diesel::sql_query(
+            "SELECT (bucket_start - $1) / $3 AS idx, SUM(tx_count)::BIGINT AS tx_count, \
+                SUM(successful)::BIGINT AS successful, SUM(failed)::BIGINT AS failed \
+            FROM metric_rollups \
+            WHERE bucket_secs = $2 AND tx_type = $4 AND bucket_start >= $1 AND bucket_start < $5 \
+                AND ($6 IS NULL OR chain_id = $6) \
+            GROUP BY idx",
+        )
+        .bind::<BigInt, _>(window.from)
+        .bind::<BigInt, _>(resolution)
+        .bind::<BigInt, _>(window.bucket)
+        .bind::<Text, _>(tx_type.unwrap_or(Tx::All).to_string())
+        .bind::<BigInt, _>(window.to)
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .load::<RollupVolume>(&mut conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted.into())
+    }
+
+    async fn delivery_latency(
+        &self,
+        chain_id: Option<u64>,
+        source_chain_id: Option<u64>,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<DeliveryLatency>, std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        diesel::sql_query(
+            "SELECT source_chain_id, destination_chain_id, COUNT(*) AS messages, \
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY latency_secs::float8) AS p50, \
+                percentile_cont(0.9) WITHIN GROUP (ORDER BY latency_secs::float8) AS p90, \
+                percentile_cont(0.99) WITHIN GROUP (ORDER BY latency_secs::float8) AS p99, \
+                MAX(latency_secs) AS max \
+            FROM bridge_messages \
+            WHERE latency_secs IS NOT NULL AND delivered_at > $1 AND delivered_at <= $2 \
+                AND ($3 IS NULL OR destination_chain_id = $3) \
+                AND ($4 IS NULL OR source_chain_id = $4) \
+            GROUP BY source_chain_id, destination_chain_id \
+            ORDER BY source_chain_id, destination_chain_id",
+        )
+        .bind::<BigInt, _>(from)
+        .bind::<BigInt, _>(to)
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .bind::<Nullable<BigInt>, _>(source_chain_id.map(|id| id as i64))
+        .load::<DeliveryLatency>(&mut conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted.into())
+    }
+
+    async fn add_dead_letter(&self, letter: DeadLetterModel) -> Result<(), std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        diesel::insert_into(dead_letters_schema)
+            .values(&letter)
+            .execute(&mut conn)
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn record_block_failure(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+        error: String,
+        failed_at: i64,
+    ) -> Result<FailedBlockModel, std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        diesel::sql_query(
+            "INSERT INTO failed_blocks (chain_id, block_number, error, failed_at) \
+            VALUES ($1, $2, $3, $4) \
+            ON CONFLICT (chain_id, block_number) DO UPDATE SET \
+                error = excluded.error, \
+                attempts = failed_blocks.attempts + 1, \
+                failed_at = excluded.failed_at \
+            RETURNING chain_id, block_number, error, attempts, skipped, failed_at",
+        )
+        .bind::<BigInt, _>(chain_id as i64)
+        .bind::<BigInt, _>(block_number as i64)
+        .bind::<Text, _>(error)
+        .bind::<BigInt, _>(failed_at)
+        .get_result::<FailedBlockModel>(&mut conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted.into())
+    }
+
+    async fn skip_failed_block(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+    ) -> Result<(), std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        diesel::sql_query(
+            "UPDATE failed_blocks SET skipped = TRUE WHERE chain_id = $1 AND block_number = $2",
+        )
+        .bind::<BigInt, _>(chain_id as i64)
+        .bind::<BigInt, _>(block_number as i64)
+        .execute(&mut conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn remove_failed_block(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+    ) -> Result<(), std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        diesel::sql_query("DELETE FROM failed_blocks WHERE chain_id = $1 AND block_number = $2")
+            .bind::<BigInt, _>(chain_id as i64)
+            .bind::<BigInt, _>(block_number as i64)
+            .execute(&mut conn)
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn get_failed_blocks(
+        &self,
+        chain_id: Option<u64>,
+    ) -> Result<Vec<FailedBlockModel>, std::io::Error> {
+        let mut conn = self
+            .pool
+            .get()
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        diesel::sql_query(
+            "SELECT chain_id, block_number, error, attempts, skipped, failed_at \
+            FROM failed_blocks \
+            WHERE ($1 IS NULL OR chain_id = $1) \
+            ORDER BY chain_id, block_number",
+        )
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .load::<FailedBlockModel>(&mut conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted.into())
+    }
+}
+
+/// Blocks per range partition of a chain's transactions. Must match the
+/// width used by the `v005_partition_transactions` migration.
+const TRANSACTION_PARTITION_BLOCKS: i64 = 1_000_000;
+
+/// Batches of at least this many transactions, e.g. during a backfill, are
+/// bulk loaded with `COPY` instead of chunked inserts.
+const COPY_THRESHOLD: usize = 2_000;
+const COPY_POOL_SIZE: usize = 2;
+
+/// Adds the rollup rows of `block`, multiplied by `sign`, to the buckets of
+/// every width in `ROLLUP_RESOLUTIONS` holding the block.
+async fn add_rollups(
+    conn: &mut AsyncPgConnection,
+    block: &BlockModel,
+    sign: i64,
+) -> Result<(), diesel::result::Error> {
+    let rows = block.rollup_rows();
+
+    diesel::sql_query(
+        "INSERT INTO metric_rollups \
+            (chain_id, bucket_secs, bucket_start, tx_type, tx_count, successful, failed, gas_used, value) \
+        SELECT $1, r.bucket_secs, $2 - ($2 % r.bucket_secs), t.tx_type, $9 * t.tx_count, \
+            $9 * (t.tx_count - t.failed), $9 * t.failed, $9 * t.gas_used, $9 * t.value::numeric \
+        FROM unnest($3::bigint[]) AS r(bucket_secs) \
+        CROSS JOIN unnest($4::text[], $5::bigint[], $6::bigint[], $7::bigint[], $8::text[]) \
+            AS t(tx_type, tx_count, failed, gas_used, value) \
+        ON CONFLICT (chain_id, bucket_secs, tx_type, bucket_start) DO UPDATE SET \
+            tx_count = metric_rollups.tx_count + excluded.tx_count, \
+            successful = metric_rollups.successful + excluded.successful, \
+            failed = metric_rollups.failed + excluded.failed, \
+            gas_used = metric_rollups.gas_used + excluded.gas_used, \
+            value = metric_rollups.value + excluded.value",
+    )
+    .bind::<BigInt, _>(block.chain_id)
+    .bind::<BigInt, _>(block.block_timestamp)
+    .bind::<Array<BigInt>, _>(ROLLUP_RESOLUTIONS.to_vec())
+    .bind::<Array<Text>, _>(rows.iter().map(|(tx, _)| tx.to_string()).collect::<Vec<_>>())
+    .bind::<Array<BigInt>, _>(rows.iter().map(|(_, c)| c.total as i64).collect::<Vec<_>>())
+    .bind::<Array<BigInt>, _>(rows.iter().map(|(_, c)| c.failed as i64).collect::<Vec<_>>())
+    .bind::<Array<BigInt>, _>(rows.iter().map(|(_, c)| c.gas_used as i64).collect::<Vec<_>>())
+    .bind::<Array<Text>, _>(rows.iter().map(|(_, c)| c.value.to_string()).collect::<Vec<_>>())
+    .bind::<BigInt, _>(sign)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DatabaseConnections, TxCounts, WriteJob, WriteQueue, WriteQueueConfig};
+    use alloy::primitives::U256;
+    use std::collections::BTreeMap;
+
+    async fn storage(chain_id: i64) -> PostgresStorage {
+        let storage = PostgresStorage::new(DatabaseConnections::init_postgres().await.unwrap());
+        let mut conn = storage.pool.get().await.unwrap();
+        for table in ["blocks", "metric_rollups", "transactions"] {
+            diesel::sql_query(format!("DELETE FROM {} WHERE chain_id = $1", table))
+                .bind::<BigInt, _>(chain_id)
+                .execute(&mut conn)
+                .await
+                .unwrap();
+        }
+
+        storage
+    }
+
+    fn block(chain_id: u64, total: u64, failed: u64) -> BlockModel {
+        let counts = TxCounts {
+            total,
+            failed,
+            gas_used: 21_000 * total,
+            value: U256::from(10),
+        };
+        BlockModel::new(
+            chain_id,
+            100,
+            "0x01".to_string(),
+            1_700_000_000,
+            total as usize,
+            &BTreeMap::from([(Tx::Native, counts)]),
+            &BTreeMap::new(),
+        )
+    }
+
+    async fn hourly_volume(
+        storage: &PostgresStorage,
+        chain_id: u64,
+        tx_type: Option<Tx>,
+    ) -> Vec<(i64, i64, i64)> {
+        let window = TimeWindow {
+            from: 1_699_999_200,
+            to: 1_700_002_800,
+            bucket: 3600,
+        };
+        storage
+            .rollup_volume(Some(chain_id), tx_type, 3600, &window)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.tx_count, row.successful, row.failed))
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn replayed_blocks_are_counted_once() {
+        let storage = storage(900_001).await;
+
+        let (first, second) = tokio::join!(
+            storage.add_block_record(block(900_001, 3, 1)),
+            storage.add_block_record(block(900_001, 3, 1)),
+        );
+        first.unwrap();
+        second.unwrap();
+        storage
+            .add_block_record(block(900_001, 3, 1))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            hourly_volume(&storage, 900_001, Some(Tx::All)).await,
+            vec![(3, 2, 1)]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn rewritten_blocks_replace_their_counts() {
+        let storage = storage(900_002).await;
+
+        storage
+            .add_block_record(block(900_002, 3, 1))
+            .await
+            .unwrap();
+        storage
+            .add_block_record(block(900_002, 5, 0))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            hourly_volume(&storage, 900_002, Some(Tx::All)).await,
+            vec![(5, 5, 0)]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn volume_without_a_tx_type_is_the_total() {
+        let storage = storage(900_003).await;
+
+        storage
+            .add_block_record(block(900_003, 3, 1))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            hourly_volume(&storage, 900_003, None).await,
+            vec![(3, 2, 1)]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn large_batches_are_copied() {
+        let storage = storage(900_004).await;
+        let txns: Vec<TxModel> = (0..COPY_THRESHOLD as i64 + 500)
+            .map(|index| TxModel::fixture(900_004, 100 + index / 100, index))
+            .collect();
+        let chain = Chain {
+            chain_id: 900_004,
+            latest_tps: 0,
+        };
+
+        storage.add_txns(chain.clone(), txns.clone()).await.unwrap();
+        // replays skip the stored rows
+        storage.add_txns(chain, txns.clone()).await.unwrap();
+
+        let mut conn = storage.pool.get().await.unwrap();
+        let stored: i64 = transactions_schema
+            .filter(transactions_schema_types::chain_id.eq(900_004))
+            .count()
+            .get_result(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(stored, txns.len() as i64);
+        assert_eq!(storage.copy_pool.status().size, 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres at DATABASE_URL"]
+    async fn failed_inserts_are_dead_lettered() {
+        let storage = Arc::new(storage(900_004).await);
+        let mut conn = storage.pool.get().await.unwrap();
+        diesel::sql_query(
+            "DELETE FROM dead_letters WHERE payload->'chain'->>'chain_id' = '900004'",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        // overflows NUMERIC(78, 0)
+        let mut txn = TxModel::fixture(900_004, 100, 0);
+        txn.value = "1e80".parse().unwrap();
+        let chain = Chain {
+            chain_id: 900_004,
+            latest_tps: 0,
+        };
+        assert!(storage
+            .add_txns(chain.clone(), vec![txn.clone()])
+            .await
+            .is_err());
+
+        let config = WriteQueueConfig {
+            max_attempts: 2,
+            backoff_ms: 1,
+            ..WriteQueueConfig::default()
+        };
+        let queue = WriteQueue::start(storage.clone(), config);
+        queue
+            .push(WriteJob::Transactions {
+                chain,
+                txns: vec![txn],
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        let attempts: Vec<i32> = dead_letters_schema
+            .filter(crate::schema::dead_letters::kind.eq("transactions"))
+            .filter(diesel::dsl::sql::<diesel::sql_types::Bool>(
+                "payload->'chain'->>'chain_id' = '900004'",
+            ))
+            .select(crate::schema::dead_letters::attempts)
+            .load(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(attempts, vec![2]);
+    }
+}
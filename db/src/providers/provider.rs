@@ -1,37 +1,65 @@
-use std::{
-    collections::BTreeMap,
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::{collections::BTreeMap, sync::Arc};
 
-use alloy::rpc::types::eth::Transaction as AlloyTx;
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
-use futures::future::join_all;
+use alloy::{primitives::U256, rpc::types::eth::Transaction as AlloyTx};
+use chrono::FixedOffset;
 use rayon::prelude::*;
 use redis::RedisResult;
-use tokio::task;
 
 use crate::{
-    cache::*,
-    schema::{
-        chains::dsl::chains as chains_schema,
-        transactions::dsl::{
-            self as transactions_schema_types, transactions as transactions_schema,
-        },
-    },
-    unix_ms_to_ist, Chain, ChainId, ConvertToHex, DatabaseConnections, Limit, Parts, Stride, Tx,
-    TxAPIResponse, TxFilter, TxIdentifier, TxModel, TxResponse, TxnSummary, Type,
+    format_timestamp, from_numeric, to_unix_ms, unix_now, BlockCounts, BlockModel,
+    BridgeDeliveryModel, BridgeEvents, BridgeSendModel, BridgedValueResponse, CacheUsage, Chain,
+    ChainId, ConvertToHex, DatabaseConnections, DeliveryLatency, FailedBlockModel,
+    FailureRateResponse, Limit, MemoryStore, MetricsBackend, MetricsStore, Parts, PostgresStorage,
+    RedisStore, Retention, RollupVolume, SourceChainId, SqliteStorage, Storage, StorageBackend,
+    TimeWindow, TpsResponse, Tx, TxAPIResponse, TxCounts, TxFilter, TxIdentifier, TxModel,
+    TxResponse, TxnSummary, Type, Window, WriteJob, WriteQueue, WriteQueueConfig, BRIDGED_IN_FIELD,
+    DEFAULT_TPS_WINDOW, MAX_TPS_WINDOW, ROLLUP_RESOLUTIONS,
 };
 
 #[derive(Clone)]
 pub struct InternalDataProvider {
-    pub dbc: DatabaseConnections,
+    pub storage: Arc<dyn Storage>,
+    pub metrics: Arc<dyn MetricsStore>,
+    pub retention: Retention,
+    /// Queue the indexed transactions, blocks and bridge events are written
+    /// through.
+    pub writes: WriteQueue,
 }
 
 impl InternalDataProvider {
-    pub async fn new() -> Result<Self, std::io::Error> {
+    pub async fn new(
+        retention: Retention,
+        storage_backend: StorageBackend,
+        metrics_backend: MetricsBackend,
+        write_queue: WriteQueueConfig,
+    ) -> Result<Self, std::io::Error> {
+        let storage: Arc<dyn Storage> = match storage_backend {
+            StorageBackend::Postgres => Arc::new(PostgresStorage::new(
+                DatabaseConnections::init_postgres().await?,
+            )),
+            StorageBackend::Sqlite => Arc::new(SqliteStorage::new(
+                DatabaseConnections::init_sqlite().await?,
+            )),
+        };
+        let metrics: Arc<dyn MetricsStore> = match metrics_backend {
+            MetricsBackend::Redis => {
+                let conn = DatabaseConnections::init_redis().await?;
+                Arc::new(
+                    RedisStore::new(conn, retention.clone())
+                        .await
+                        .map_err(|e| std::io::Error::other(e.to_string()))?,
+                )
+            }
+            MetricsBackend::Memory => Arc::new(MemoryStore::new(retention.clone())),
+        };
+
+        let writes = WriteQueue::start(storage.clone(), write_queue);
+
         Ok(InternalDataProvider {
-            dbc: DatabaseConnections::init().await?,
+            storage,
+            metrics,
+            retention,
+            writes,
         })
     }
 
@@ -43,55 +71,40 @@ impl InternalDataProvider {
         tx_type: Type,
         limit: Limit,
     ) -> Result<Vec<TxAPIResponse>, std::io::Error> {
-        let mut conn = self
-            .dbc
-            .postgres
-            .get()
-            .await
-            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
-
-        let mut query = transactions_schema.into_boxed();
-        query = query.order(transactions_schema_types::block_number.desc());
-        if let Some(chain_id) = filter.chain_id.as_ref() {
-            query = query.filter(transactions_schema_types::chain_id.eq(chain_id.clone() as i64));
-        }
-        if let Some(tx_hash) = identifier.tx_hash.as_ref() {
-            query = query.filter(transactions_schema_types::transaction_hash.eq(tx_hash));
-        }
-        if let Some(tpe) = tx_type.tx_type.as_ref() {
-            match tpe {
-                Tx::Native | Tx::CrossChain => {
-                    query = query.filter(transactions_schema_types::tx_type.eq(tpe.to_string()));
-                }
-                Tx::All => {}
-            };
-        }
-
-        let result: Vec<TxModel> = query
-            .limit(limit.limit.unwrap_or(10) as i64)
-            .offset((identifier.page_idx.unwrap_or(0) * limit.limit.unwrap_or(10)) as i64)
-            .select(TxModel::as_select())
-            .load(&mut conn)
-            .await
-            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+        let result = self
+            .storage
+            .get_txs(
+                &identifier,
+                &filter,
+                tx_type.tx_type,
+                limit.limit.unwrap_or(10),
+            )
+            .await?;
 
         let results: Vec<TxAPIResponse> = result
             .into_par_iter()
-            .map(|tx| {
-                if let Some(_) = parts.all {
-                    let tx: AlloyTx = tx.into();
-                    TxAPIResponse::Transaction(tx)
+            .filter_map(|tx| {
+                if parts.all.is_some() {
+                    let hash = tx.transaction_hash.as_slice().to_hex_string();
+                    match AlloyTx::try_from(tx) {
+                        Ok(tx) => Some(TxAPIResponse::Transaction(Box::new(tx))),
+                        Err(e) => {
+                            // a bad row is left out rather than failing the page
+                            tracing::warn!("Skipping stored transaction {}: {}", hash, e);
+                            None
+                        }
+                    }
                 } else {
                     let txn_summary = TxnSummary {
-                        hash: tx.transaction_hash,
-                        block_hash: tx.block_hash,
-                        from: tx._from.clone(),
-                        to: tx._to,
+                        hash: tx.transaction_hash.as_slice().to_hex_string(),
+                        block_hash: tx.block_hash.map(|x| x.as_slice().to_hex_string()),
+                        from: tx._from.as_slice().to_hex_string(),
+                        to: tx._to.map(|x| x.as_slice().to_hex_string()),
                         status: Some(1),
-                        value: tx.value,
-                        block_height: tx.block_number.unwrap() as u64,
+                        value: from_numeric(&tx.value).to_hex_string(),
+                        block_height: tx.block_number as u64,
                     };
-                    TxAPIResponse::TxnSummary(txn_summary)
+                    Some(TxAPIResponse::TxnSummary(txn_summary))
                 }
             })
             .collect();
@@ -99,6 +112,7 @@ impl InternalDataProvider {
         Ok(results)
     }
 
+    /// Queues the transactions of a block for writing.
     pub async fn add_txns(
         &self,
         chain_id: u64,
@@ -107,285 +121,486 @@ impl InternalDataProvider {
         tx_map: BTreeMap<String, Tx>,
     ) -> Result<(), std::io::Error> {
         let txns: Vec<TxModel> = transactions
-            .iter()
-            .take(10)
-            .cloned()
-            .collect::<Vec<_>>()
             .into_par_iter()
             .map(|transaction| {
                 let tx_type = tx_map
                     .get(&transaction.hash.to_hex_string())
                     .unwrap_or(&Tx::Native);
-                TxModel::from(chain_id, &transaction, tx_type)
+                TxModel::from(chain_id, transaction, tx_type)
             })
             .collect();
 
-        {
-            let mut conn = self
-                .dbc
-                .postgres
-                .get()
-                .await
-                .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
-
-            diesel::insert_into(chains_schema)
-                .values(&Chain {
+        self.writes
+            .push(WriteJob::Transactions {
+                chain: Chain {
                     chain_id: chain_id as i64,
                     latest_tps: tx_count as i64,
-                })
-                .on_conflict(crate::schema::chains::chain_id)
-                .do_update()
-                .set(crate::schema::chains::latest_tps.eq(tx_count as i64))
-                .execute(&mut conn)
-                .await
-                .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
-        }
-
-        let mut tasks = Vec::new();
-        for chunk in txns.chunks(250) {
-            let chunk = chunk.to_vec();
-            let db_pool = self.dbc.postgres.clone();
-            let task = task::spawn(async move {
-                let mut conn = db_pool
-                    .get()
-                    .await
-                    .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
-
-                diesel::insert_into(transactions_schema)
-                    .values(&chunk)
-                    .on_conflict((
-                        crate::schema::transactions::chain_id,
-                        crate::schema::transactions::transaction_hash,
-                    ))
-                    .do_nothing()
-                    .execute(&mut conn)
-                    .await
-                    .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
-
-                Ok::<(), std::io::Error>(())
-            });
-
-            tasks.push(task);
-        }
-
-        let results = join_all(tasks).await;
-        for res in results {
-            if let Err(e) = res {
-                eprintln!("Task failed: {:?}", e);
-            }
-        }
-
-        Ok(())
+                },
+                txns,
+            })
+            .await
     }
 
+    /// Writes the counts and bridged value of a block.
     pub async fn add_block(
         &self,
         chain_id: &u64,
         timestamp: i64,
-        successful_xfers: u64,
-        total_xfers: u64,
-        total_native_transfers: u64,
-        total_x_chain_transfers: u64,
+        category_counts: &BTreeMap<Tx, TxCounts>,
+        received_by_source: &BTreeMap<u64, U256>,
         tx_count: usize,
         height: u64,
     ) -> RedisResult<()> {
-        {
-            let mut redis_conn = self.dbc.redis.lock().await;
-            add_block(
+        self.metrics
+            .add_block(
                 chain_id,
                 timestamp,
-                successful_xfers,
-                total_xfers,
-                total_native_transfers,
-                total_x_chain_transfers,
+                category_counts,
+                received_by_source,
                 tx_count as u64,
                 height,
-                &mut redis_conn,
             )
+            .await
+    }
+
+    /// Writes the counts of many blocks in a single round-trip.
+    pub async fn add_blocks(&self, blocks: &[BlockCounts]) -> RedisResult<()> {
+        self.metrics.add_blocks(blocks).await
+    }
+
+    /// Rolls up and trims the series of every chain. Returns the number of
+    /// trimmed blocks.
+    pub async fn trim_metrics(&self) -> RedisResult<usize> {
+        let mut trimmed = 0;
+        for chain_id in self.metrics.get_chains().await? {
+            trimmed += self.metrics.trim_chain(&chain_id, unix_now()).await?;
+        }
+
+        Ok(trimmed)
+    }
+
+    pub async fn cache_usage(&self, identifier: ChainId) -> RedisResult<Vec<CacheUsage>> {
+        let chain_ids: Vec<u64> = match identifier.chain_id {
+            Some(chain_id) => vec![chain_id],
+            None => self.metrics.get_chains().await?,
+        };
+
+        let mut usage = Vec::with_capacity(chain_ids.len());
+        for chain_id in &chain_ids {
+            usage.push(self.metrics.get_cache_usage(chain_id).await?);
         }
+
+        Ok(usage)
     }
 
     pub async fn get_latest_height(&self, id: &u64) -> RedisResult<u64> {
-        let height = {
-            let mut redis_conn = self.dbc.redis.lock().await;
-            match get_latest_height(id, &mut redis_conn) {
-                Ok(ht) => Ok(ht),
-                Err(_) => Ok(0),
-            }
+        match self.metrics.get_latest_height(id).await {
+            Ok(ht) => Ok(ht),
+            Err(_) => Ok(0),
+        }
+    }
+
+    /// Ids of the chains with indexed blocks.
+    pub async fn get_chains(&self) -> RedisResult<Vec<u64>> {
+        self.metrics.get_chains().await
+    }
+
+    /// Height and unix time in milliseconds of the latest indexed block of a
+    /// chain, if any.
+    pub async fn indexed_head(&self, chain_id: &u64) -> Option<(u64, i64)> {
+        let height = self.metrics.get_latest_height(chain_id).await.ok()?;
+        let timestamp = self.metrics.get_latest_timestamp(chain_id).await.ok()?;
+
+        Some((height, to_unix_ms(timestamp)))
+    }
+
+    /// Timestamp, in unix seconds, of the latest indexed block of a chain, or
+    /// of the most recent one across chains. Falls back to now.
+    pub async fn latest_timestamp(&self, identifier: &ChainId) -> i64 {
+        let latest_timestamp = if let Some(chain_id) = identifier.chain_id {
+            self.metrics.get_latest_timestamp(&chain_id).await.ok()
+        } else {
+            self.metrics.get_all_chains_latest_timestamp().await.ok()
         };
 
-        height
+        latest_timestamp
+            .map(|timestamp| to_unix_ms(timestamp) / 1000)
+            .unwrap_or_else(unix_now)
     }
 
     pub async fn live_tps(
         &self,
         identifier: ChainId,
-        stride: Stride,
         tx_type: Type,
-    ) -> RedisResult<Vec<(u64, String)>> {
-        let tps_with_timestamps = {
-            let mut redis_conn = self.dbc.redis.lock().await;
-            if let Some(chain_id) = identifier.chain_id {
-                get_live_tps(&chain_id, stride, tx_type, &mut redis_conn)?
-            } else {
-                get_all_chains_live_tps_in_range(stride, tx_type, &mut redis_conn)?
-            }
+        window: TimeWindow,
+        offset: FixedOffset,
+    ) -> RedisResult<Vec<(f64, i64, String)>> {
+        let tps_with_timestamps = if let Some(chain_id) = identifier.chain_id {
+            self.metrics
+                .get_live_tps(&chain_id, &window, tx_type)
+                .await?
+        } else {
+            self.metrics
+                .get_all_chains_live_tps_in_range(&window, tx_type)
+                .await?
         };
 
-        Ok(tps_with_timestamps)
+        tps_with_timestamps
+            .into_iter()
+            .map(|(tps, timestamp_ms)| {
+                let time = format_timestamp(timestamp_ms, &offset)
+                    .map_err(|e| (redis::ErrorKind::TypeError, "invalid block timestamp", e))?;
+                Ok((tps, timestamp_ms, time))
+            })
+            .collect()
     }
 
-    pub async fn current_tps(&self, identifier: ChainId, tx_type: Type) -> RedisResult<u64> {
-        let tps = {
-            let mut redis_conn = self.dbc.redis.lock().await;
-            let tps = if let Some(chain_id) = identifier.chain_id {
-                get_latest_tps(&chain_id, tx_type, &mut redis_conn)?
-            } else {
-                get_all_chains_tps_in_range(tx_type, &mut redis_conn)?
-            };
-            tps as u64
+    pub async fn current_tps(
+        &self,
+        identifier: ChainId,
+        tx_type: Type,
+        window: Window,
+    ) -> RedisResult<TpsResponse> {
+        let window = window
+            .window
+            .unwrap_or(DEFAULT_TPS_WINDOW)
+            .clamp(1, MAX_TPS_WINDOW);
+
+        let tps = if let Some(chain_id) = identifier.chain_id {
+            self.metrics
+                .get_latest_tps(&chain_id, window, tx_type)
+                .await?
+        } else {
+            self.metrics
+                .get_all_chains_tps_in_range(window, tx_type)
+                .await?
         };
 
         Ok(tps)
     }
 
-    pub async fn total_xfers_last_day(
+    pub async fn total_xfers(
         &self,
         identifier: ChainId,
         tx_type: Type,
+        window: TimeWindow,
     ) -> RedisResult<u64> {
-        let tps = {
-            let mut redis_conn = self.dbc.redis.lock().await;
-            let tps = if let Some(chain_id) = identifier.chain_id {
-                let latest_timestamp = get_latest_timestamp(&chain_id, &mut redis_conn)?;
-                get_successful_xfers_in_range(
-                    &chain_id,
-                    86400,
-                    latest_timestamp,
-                    tx_type,
-                    &mut redis_conn,
-                )?
-            } else {
-                let now_duration = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("SystemTime before UNIX EPOCH!");
-                get_all_chains_success_xfers_in_range(
-                    86400,
-                    now_duration.as_secs() as i64,
-                    tx_type,
-                    &mut redis_conn,
-                )?
-            };
-
-            tps as u64
+        let xfers = if let Some(chain_id) = identifier.chain_id {
+            self.metrics
+                .get_total_xfers_in_range(&chain_id, window.span(), window.to, tx_type)
+                .await?
+        } else {
+            self.metrics
+                .get_all_chains_total_xfers_in_range(window.span(), window.to, tx_type)
+                .await?
         };
 
-        Ok(tps)
+        Ok(xfers)
     }
 
-    pub async fn successful_xfers_last_day(
+    pub async fn successful_xfers(
         &self,
         identifier: ChainId,
         tx_type: Type,
+        window: TimeWindow,
     ) -> RedisResult<u64> {
-        let xfers = {
-            let mut redis_conn = self.dbc.redis.lock().await;
-            let xfers = if let Some(chain_id) = identifier.chain_id {
-                let latest_timestamp = get_latest_timestamp(&chain_id, &mut redis_conn)?;
-                get_successful_xfers_in_range(
-                    &chain_id,
-                    86400,
-                    latest_timestamp,
-                    tx_type,
-                    &mut redis_conn,
-                )
+        let xfers = if let Some(chain_id) = identifier.chain_id {
+            self.metrics
+                .get_successful_xfers_in_range(&chain_id, window.span(), window.to, tx_type)
+                .await
                 .unwrap_or(0)
-            } else {
-                let now_duration = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("SystemTime before UNIX EPOCH!");
-                get_all_chains_success_xfers_in_range(
-                    86400,
-                    now_duration.as_secs() as i64,
-                    tx_type,
-                    &mut redis_conn,
-                )
+        } else {
+            self.metrics
+                .get_all_chains_success_xfers_in_range(window.span(), window.to, tx_type)
+                .await
                 .unwrap_or(0)
-            };
-
-            xfers as u64
         };
 
         Ok(xfers)
     }
 
+    pub async fn failure_rate(
+        &self,
+        identifier: ChainId,
+        tx_type: Type,
+        window: TimeWindow,
+    ) -> RedisResult<FailureRateResponse> {
+        let total = self
+            .total_xfers(identifier.clone(), tx_type.clone(), window)
+            .await?;
+        let successful = self.successful_xfers(identifier, tx_type, window).await?;
+
+        let failed = total.saturating_sub(successful);
+        Ok(FailureRateResponse {
+            successful_txns: successful,
+            failed_txns: failed,
+            total_txns: total,
+            failure_rate: if total == 0 {
+                0.0
+            } else {
+                failed as f64 / total as f64
+            },
+        })
+    }
+
+    /// Volume per bucket of `window`. Ranges reaching past the raw metrics
+    /// retention are served from the persisted rollups.
     pub async fn transaction_volume(
         &self,
         identifier: ChainId,
         tx_type: Type,
-        stride: Stride,
-    ) -> RedisResult<Vec<TxResponse>> {
-        let mut tx_response = Vec::new();
+        window: TimeWindow,
+        offset: FixedOffset,
+    ) -> Result<Vec<TxResponse>, std::io::Error> {
+        if window.from < unix_now().saturating_sub(self.retention.raw_secs) {
+            return self
+                .rollup_volume(identifier, tx_type, window, offset)
+                .await;
+        }
 
-        {
-            let mut redis_conn = self.dbc.redis.lock().await;
-            let (interval, width) = if let Some(stride) = stride.stride {
-                if stride == 1 {
-                    // 1 hr
-                    (30, 120) // interval: 30 sec (30 * 120s = 1 hr)
-                } else {
-                    // 10 min
-                    (10, 60) // interval: 10 sec (10 * 60s = 10 min)
-                }
+        let mut tx_response = Vec::new();
+        for (start, end) in window.buckets() {
+            let (success, total) = if let Some(chain_id) = identifier.chain_id {
+                let success = self
+                    .metrics
+                    .get_successful_xfers_in_range(&chain_id, end - start, end, tx_type.clone())
+                    .await
+                    .unwrap_or(0);
+                let total = self
+                    .metrics
+                    .get_total_xfers_in_range(&chain_id, end - start, end, tx_type.clone())
+                    .await
+                    .unwrap_or(0);
+                (success, total)
             } else {
-                (3600, 24) // interval: 15 min (24 * 1hr = 24hr)
+                let success = self
+                    .metrics
+                    .get_all_chains_success_xfers_in_range(end - start, end, tx_type.clone())
+                    .await
+                    .unwrap_or(0);
+                let total = self
+                    .metrics
+                    .get_all_chains_total_xfers_in_range(end - start, end, tx_type.clone())
+                    .await
+                    .unwrap_or(0);
+                (success, total)
             };
 
-            if let Some(chain_id) = identifier.chain_id {
-                let latest_timestamp = get_latest_timestamp(&chain_id, &mut redis_conn)?;
-
-                for i in 1..width {
-                    // 96 times, so iterate till last 24 hr data (96 * 15min = 24hr)
-                    let success = get_successful_xfers_in_range(
-                        &chain_id,
-                        i * interval,
-                        latest_timestamp.saturating_sub((i - 1) * interval),
-                        tx_type.clone(),
-                        &mut redis_conn,
-                    )
-                    .unwrap_or(0);
+            tx_response.push(TxResponse {
+                successful_txns: success,
+                failed_txns: total.saturating_sub(success),
+                total_txns: total,
+                timestamp: start * 1000,
+                time: format_timestamp(start, &offset)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+            })
+        }
 
-                    tx_response.push(TxResponse {
-                        successful_txns: success as u64,
-                        total_txns: success as u64,
-                        timestamp: unix_ms_to_ist(latest_timestamp.saturating_sub(i * interval)),
-                    })
-                }
-            } else {
-                let now_duration = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("SystemTime before UNIX EPOCH!");
-                let latest_timestamp = now_duration.as_secs() as i64;
-                for i in 1..width {
-                    let success = get_all_chains_success_xfers_in_range(
-                        i * interval,
-                        latest_timestamp.saturating_sub((i - 1) * interval),
-                        tx_type.clone(),
-                        &mut redis_conn,
-                    )
-                    .unwrap_or(0);
+        Ok(tx_response)
+    }
 
-                    tx_response.push(TxResponse {
-                        successful_txns: success as u64,
-                        total_txns: success as u64,
-                        timestamp: unix_ms_to_ist(latest_timestamp.saturating_sub(i * interval)),
-                    })
-                }
+    pub async fn bridged_value(
+        &self,
+        identifier: ChainId,
+        window: TimeWindow,
+        offset: FixedOffset,
+    ) -> RedisResult<Vec<BridgedValueResponse>> {
+        let mut value_response = Vec::new();
+        for (start, end) in window.buckets() {
+            let (received, sent) = if let Some(chain_id) = identifier.chain_id {
+                let received = self
+                    .metrics
+                    .get_bridged_value_in_range(&chain_id, BRIDGED_IN_FIELD, end - start, end)
+                    .await
+                    .unwrap_or(U256::ZERO);
+                let sent = self
+                    .metrics
+                    .get_bridged_out_in_range(&chain_id, end - start, end)
+                    .await
+                    .unwrap_or(U256::ZERO);
+                (received, sent)
+            } else {
+                // every bridged amount is received exactly once, so the
+                // inbound total is also the outbound total
+                let bridged = self
+                    .metrics
+                    .get_all_chains_bridged_value_in_range(end - start, end)
+                    .await
+                    .unwrap_or(U256::ZERO);
+                (bridged, bridged)
             };
-        };
 
-        tx_response.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+            value_response.push(BridgedValueResponse {
+                received: received.to_string(),
+                sent: sent.to_string(),
+                timestamp: start * 1000,
+                time: format_timestamp(start, &offset)
+                    .map_err(|e| (redis::ErrorKind::TypeError, "invalid bucket timestamp", e))?,
+            })
+        }
 
-        Ok(tx_response)
+        Ok(value_response)
+    }
+
+    pub async fn add_bridge_events(
+        &self,
+        events: BridgeEvents,
+        block_number: u64,
+        timestamp: i64,
+    ) -> Result<(), std::io::Error> {
+        let sends: Vec<BridgeSendModel> = events
+            .sends
+            .iter()
+            .map(|message| BridgeSendModel::new(message, block_number, timestamp))
+            .collect();
+        let deliveries: Vec<BridgeDeliveryModel> = events
+            .deliveries
+            .iter()
+            .map(|message| BridgeDeliveryModel::new(message, block_number, timestamp))
+            .collect();
+
+        self.writes
+            .push(WriteJob::BridgeEvents { sends, deliveries })
+            .await
+    }
+
+    /// Fills in the latency of every message whose send and delivery have both
+    /// been indexed, returning the number of newly matched messages.
+    pub async fn match_bridge_messages(&self) -> Result<usize, std::io::Error> {
+        self.storage.match_bridge_messages().await
+    }
+
+    /// Records a failed attempt at processing a block and returns its total
+    /// number of attempts.
+    pub async fn record_block_failure(
+        &self,
+        chain_id: u64,
+        height: u64,
+        error: String,
+    ) -> Result<u32, std::io::Error> {
+        let failure = self
+            .storage
+            .record_block_failure(chain_id, height, error, unix_now())
+            .await?;
+
+        Ok(failure.attempts as u32)
+    }
+
+    pub async fn skip_failed_block(
+        &self,
+        chain_id: u64,
+        height: u64,
+    ) -> Result<(), std::io::Error> {
+        self.storage.skip_failed_block(chain_id, height).await
+    }
+
+    pub async fn remove_failed_block(
+        &self,
+        chain_id: u64,
+        height: u64,
+    ) -> Result<(), std::io::Error> {
+        self.storage.remove_failed_block(chain_id, height).await
+    }
+
+    pub async fn get_failed_blocks(
+        &self,
+        chain_id: Option<u64>,
+    ) -> Result<Vec<FailedBlockModel>, std::io::Error> {
+        self.storage.get_failed_blocks(chain_id).await
+    }
+
+    pub async fn add_block_record(&self, block: BlockModel) -> Result<(), std::io::Error> {
+        self.writes.push(WriteJob::BlockRecord { block }).await
+    }
+
+    /// Rewrites the metrics of the blocks with a timestamp in `(from, to]`
+    /// from their persisted records. Returns the number of restored blocks.
+    pub async fn rebuild_cache(
+        &self,
+        chain_id: Option<u64>,
+        from: i64,
+        to: i64,
+    ) -> Result<usize, std::io::Error> {
+        let mut restored = 0;
+        loop {
+            let blocks = self
+                .storage
+                .get_block_records(chain_id, from, to, restored as i64, REBUILD_BATCH_SIZE)
+                .await?;
+            if blocks.is_empty() {
+                break;
+            }
+
+            self.metrics
+                .restore_blocks(&blocks)
+                .await
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+            restored += blocks.len();
+        }
+
+        Ok(restored)
+    }
+
+    /// Volume per bucket of `window` from the coarsest rollup resolution that
+    /// divides the bucket width.
+    pub async fn rollup_volume(
+        &self,
+        identifier: ChainId,
+        tx_type: Type,
+        window: TimeWindow,
+        offset: FixedOffset,
+    ) -> Result<Vec<TxResponse>, std::io::Error> {
+        let resolution = ROLLUP_RESOLUTIONS
+            .iter()
+            .rev()
+            .find(|resolution| window.bucket % **resolution == 0)
+            .copied()
+            .unwrap_or(ROLLUP_RESOLUTIONS[0]);
+
+        let volumes = self
+            .storage
+            .rollup_volume(identifier.chain_id, tx_type.tx_type, resolution, &window)
+            .await?;
+
+        let volumes: BTreeMap<i64, RollupVolume> = volumes
+            .into_iter()
+            .map(|volume| (volume.idx, volume))
+            .collect();
+
+        window
+            .buckets()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (start, _))| {
+                let volume = volumes.get(&(idx as i64));
+                Ok(TxResponse {
+                    successful_txns: volume.map_or(0, |v| v.successful as u64),
+                    failed_txns: volume.map_or(0, |v| v.failed as u64),
+                    total_txns: volume.map_or(0, |v| v.tx_count as u64),
+                    timestamp: start * 1000,
+                    time: format_timestamp(start, &offset)
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn delivery_latency(
+        &self,
+        identifier: ChainId,
+        source: SourceChainId,
+        window: TimeWindow,
+    ) -> Result<Vec<DeliveryLatency>, std::io::Error> {
+        self.storage
+            .delivery_latency(
+                identifier.chain_id,
+                source.source_chain_id,
+                window.from,
+                window.to,
+            )
+            .await
     }
 }
+
+const REBUILD_BATCH_SIZE: i64 = 1000;
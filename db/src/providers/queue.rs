@@ -0,0 +1,441 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    Mutex, Notify,
+};
+use tracing::{error, warn};
+
+use crate::{
+    BlockModel, BridgeDeliveryModel, BridgeSendModel, Chain, DeadLetterModel, Storage, TxModel,
+    WriteQueueConfig,
+};
+
+/// Write handed from the fetch loop to persistence. Every write is an upsert
+/// or skips stored rows, so it can be retried and replayed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WriteJob {
+    Transactions {
+        chain: Chain,
+        txns: Vec<TxModel>,
+    },
+    BlockRecord {
+        block: BlockModel,
+    },
+    BridgeEvents {
+        sends: Vec<BridgeSendModel>,
+        deliveries: Vec<BridgeDeliveryModel>,
+    },
+}
+
+impl WriteJob {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WriteJob::Transactions { .. } => "transactions",
+            WriteJob::BlockRecord { .. } => "block_record",
+            WriteJob::BridgeEvents { .. } => "bridge_events",
+        }
+    }
+
+    pub async fn write(&self, storage: &dyn Storage) -> Result<(), std::io::Error> {
+        match self.clone() {
+            WriteJob::Transactions { chain, txns } => storage.add_txns(chain, txns).await,
+            WriteJob::BlockRecord { block } => storage.add_block_record(block).await,
+            WriteJob::BridgeEvents { sends, deliveries } => {
+                storage.add_bridge_events(sends, deliveries).await
+            }
+        }
+    }
+}
+
+/// Bounded queue of writes drained by a pool of workers. Producers wait while
+/// it is full, so fetching slows down to the pace of the database.
+///
+/// The queue is held in memory only: writes still queued when the process
+/// stops are lost, although their blocks were already counted in the metrics
+/// store. They are recovered by restarting from an earlier height, which
+/// replays them idempotently.
+#[derive(Clone)]
+pub struct WriteQueue {
+    sender: mpsc::Sender<WriteJob>,
+    pending: Arc<Pending>,
+}
+
+/// Jobs pushed but not yet written or dead-lettered.
+#[derive(Default)]
+struct Pending {
+    count: AtomicUsize,
+    drained: Notify,
+}
+
+impl Pending {
+    fn done(&self, jobs: usize) {
+        if self.count.fetch_sub(jobs, Ordering::SeqCst) == jobs {
+            self.drained.notify_waiters();
+        }
+    }
+}
+
+impl WriteQueue {
+    /// Starts the workers writing the queued jobs to `storage`.
+    pub fn start(storage: Arc<dyn Storage>, config: WriteQueueConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.capacity.max(1));
+        let receiver = Arc::new(Mutex::new(receiver));
+        let pending = Arc::new(Pending::default());
+
+        for _ in 0..config.workers.max(1) {
+            let storage = storage.clone();
+            let receiver = receiver.clone();
+            let config = config.clone();
+            let pending = pending.clone();
+            tokio::spawn(async move {
+                // job taken off the queue while batching, written next
+                let mut next = None;
+                loop {
+                    let (job, jobs) = {
+                        let mut receiver = receiver.lock().await;
+                        let job = match next.take() {
+                            Some(job) => job,
+                            None => match receiver.recv().await {
+                                Some(job) => job,
+                                None => break,
+                            },
+                        };
+                        batch_txns(job, &mut receiver, config.max_batch_txns, &mut next)
+                    };
+                    write_with_retries(storage.as_ref(), job, &config).await;
+                    pending.done(jobs);
+                }
+            });
+        }
+
+        Self { sender, pending }
+    }
+
+    /// Queues a write, waiting for a free slot while the queue is full.
+    pub async fn push(&self, job: WriteJob) -> Result<(), std::io::Error> {
+        self.pending.count.fetch_add(1, Ordering::SeqCst);
+        let job = match self.sender.try_send(job) {
+            Ok(()) => return Ok(()),
+            Err(TrySendError::Full(job)) => job,
+            Err(TrySendError::Closed(_)) => {
+                self.pending.done(1);
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+        };
+
+        warn!(
+            "Write queue full with {} pending writes, waiting",
+            self.sender.max_capacity()
+        );
+        self.sender.send(job).await.map_err(|_| {
+            self.pending.done(1);
+            std::io::ErrorKind::BrokenPipe.into()
+        })
+    }
+
+    /// Waits until every queued write has been written or dead-lettered.
+    pub async fn flush(&self) {
+        loop {
+            let drained = self.pending.drained.notified();
+            if self.pending.count.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            drained.await;
+        }
+    }
+}
+
+/// Appends the transactions of the jobs already queued behind `job` for the
+/// same chain, while the batch stays within `max_txns` rows. The first job
+/// that does not fit is left in `next`. Returns the batch and the number of
+/// jobs merged into it.
+fn batch_txns(
+    job: WriteJob,
+    receiver: &mut mpsc::Receiver<WriteJob>,
+    max_txns: usize,
+    next: &mut Option<WriteJob>,
+) -> (WriteJob, usize) {
+    let WriteJob::Transactions {
+        mut chain,
+        mut txns,
+    } = job
+    else {
+        return (job, 1);
+    };
+
+    let mut jobs = 1;
+    while let Ok(queued) = receiver.try_recv() {
+        match queued {
+            WriteJob::Transactions {
+                chain: queued_chain,
+                txns: queued_txns,
+            } if queued_chain.chain_id == chain.chain_id
+                && txns.len() + queued_txns.len() <= max_txns =>
+            {
+                chain = queued_chain;
+                txns.extend(queued_txns);
+                jobs += 1;
+            }
+            queued => {
+                *next = Some(queued);
+                break;
+            }
+        }
+    }
+
+    (WriteJob::Transactions { chain, txns }, jobs)
+}
+
+/// Writes a job, retrying with exponential backoff, and records it as a dead
+/// letter once `max_attempts` have failed.
+async fn write_with_retries(storage: &dyn Storage, job: WriteJob, config: &WriteQueueConfig) {
+    let max_attempts = config.max_attempts.max(1);
+    let mut attempts = 0;
+    let error = loop {
+        attempts += 1;
+        match job.write(storage).await {
+            Ok(()) => return,
+            Err(e) if attempts < max_attempts => {
+                let backoff = config
+                    .backoff_ms
+                    .saturating_mul(1 << (attempts - 1).min(MAX_BACKOFF_DOUBLINGS));
+                warn!(
+                    "Failed to write {} (attempt {}/{}), retrying in {}ms: {}",
+                    job.kind(),
+                    attempts,
+                    max_attempts,
+                    backoff,
+                    e
+                );
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+            }
+            Err(e) => break e,
+        }
+    };
+
+    error!(
+        "Failed to write {} after {} attempts, moving it to the dead letters: {}",
+        job.kind(),
+        attempts,
+        error
+    );
+    let letter = DeadLetterModel {
+        kind: job.kind().to_string(),
+        payload: serde_json::to_value(&job).unwrap_or_default(),
+        error: error.to_string(),
+        attempts: attempts as i32,
+    };
+    if let Err(e) = storage.add_dead_letter(letter).await {
+        error!(
+            "Failed to record dead letter, dropping {}: {}",
+            job.kind(),
+            e
+        );
+    }
+}
+
+const MAX_BACKOFF_DOUBLINGS: u32 = 10;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DeliveryLatency, FailedBlockModel, RollupVolume, TimeWindow, Tx, TxFilter, TxIdentifier,
+    };
+    use async_trait::async_trait;
+
+    /// Storage recording the size of every transaction write, failing them
+    /// when `failing` is set.
+    #[derive(Default)]
+    struct RecordingStorage {
+        failing: bool,
+        batches: std::sync::Mutex<Vec<usize>>,
+        dead_letters: std::sync::Mutex<Vec<DeadLetterModel>>,
+    }
+
+    #[async_trait]
+    impl Storage for RecordingStorage {
+        async fn get_txs(
+            &self,
+            _: &TxIdentifier,
+            _: &TxFilter,
+            _: Option<Tx>,
+            _: u64,
+        ) -> Result<Vec<TxModel>, std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn add_txns(&self, _: Chain, txns: Vec<TxModel>) -> Result<(), std::io::Error> {
+            self.batches.lock().unwrap().push(txns.len());
+            if self.failing {
+                return Err(std::io::Error::other("duplicate key value"));
+            }
+            Ok(())
+        }
+
+        async fn add_bridge_events(
+            &self,
+            _: Vec<BridgeSendModel>,
+            _: Vec<BridgeDeliveryModel>,
+        ) -> Result<(), std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn match_bridge_messages(&self) -> Result<usize, std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn add_block_record(&self, _: BlockModel) -> Result<(), std::io::Error> {
+            Ok(())
+        }
+
+        async fn get_block_records(
+            &self,
+            _: Option<u64>,
+            _: i64,
+            _: i64,
+            _: i64,
+            _: i64,
+        ) -> Result<Vec<BlockModel>, std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn rollup_volume(
+            &self,
+            _: Option<u64>,
+            _: Option<Tx>,
+            _: i64,
+            _: &TimeWindow,
+        ) -> Result<Vec<RollupVolume>, std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn delivery_latency(
+            &self,
+            _: Option<u64>,
+            _: Option<u64>,
+            _: i64,
+            _: i64,
+        ) -> Result<Vec<DeliveryLatency>, std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn add_dead_letter(&self, letter: DeadLetterModel) -> Result<(), std::io::Error> {
+            self.dead_letters.lock().unwrap().push(letter);
+            Ok(())
+        }
+
+        async fn record_block_failure(
+            &self,
+            _: u64,
+            _: u64,
+            _: String,
+            _: i64,
+        ) -> Result<FailedBlockModel, std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn skip_failed_block(&self, _: u64, _: u64) -> Result<(), std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn remove_failed_block(&self, _: u64, _: u64) -> Result<(), std::io::Error> {
+            unimplemented!()
+        }
+
+        async fn get_failed_blocks(
+            &self,
+            _: Option<u64>,
+        ) -> Result<Vec<FailedBlockModel>, std::io::Error> {
+            unimplemented!()
+        }
+    }
+
+    fn txns_job(chain_id: i64, count: usize) -> WriteJob {
+        let txns = (0..count)
+            .map(|index| TxModel::fixture(chain_id, 1, index as i64))
+            .collect();
+
+        WriteJob::Transactions {
+            chain: Chain {
+                chain_id,
+                latest_tps: count as i64,
+            },
+            txns,
+        }
+    }
+
+    async fn drain(storage: &Arc<RecordingStorage>, jobs: Vec<WriteJob>, max_batch_txns: usize) {
+        let config = WriteQueueConfig {
+            workers: 1,
+            max_attempts: 3,
+            backoff_ms: 1,
+            max_batch_txns,
+            ..WriteQueueConfig::default()
+        };
+        let queue = WriteQueue::start(storage.clone(), config);
+        for job in jobs {
+            queue.push(job).await.unwrap();
+        }
+        queue.flush().await;
+    }
+
+    #[tokio::test]
+    async fn queued_transactions_of_a_chain_are_batched() {
+        let storage = Arc::new(RecordingStorage::default());
+
+        let jobs = vec![txns_job(1, 2), txns_job(1, 2), txns_job(1, 2)];
+        drain(&storage, jobs, 4).await;
+
+        assert_eq!(*storage.batches.lock().unwrap(), vec![4, 2]);
+    }
+
+    #[tokio::test]
+    async fn batches_stop_at_other_chains_and_jobs() {
+        let storage = Arc::new(RecordingStorage::default());
+
+        let block = BlockModel::new(
+            1,
+            1,
+            String::new(),
+            0,
+            0,
+            &Default::default(),
+            &Default::default(),
+        );
+        let jobs = vec![
+            txns_job(1, 2),
+            txns_job(2, 2),
+            WriteJob::BlockRecord { block },
+            txns_job(2, 2),
+        ];
+        drain(&storage, jobs, 100).await;
+
+        assert_eq!(*storage.batches.lock().unwrap(), vec![2, 2, 2]);
+    }
+
+    #[tokio::test]
+    async fn failed_writes_are_retried_then_dead_lettered() {
+        let storage = Arc::new(RecordingStorage {
+            failing: true,
+            ..Default::default()
+        });
+
+        drain(&storage, vec![txns_job(1, 2)], 100).await;
+
+        assert_eq!(*storage.batches.lock().unwrap(), vec![2, 2, 2]);
+        let dead_letters = storage.dead_letters.lock().unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].kind, "transactions");
+        assert_eq!(dead_letters[0].attempts, 3);
+        assert_eq!(dead_letters[0].error, "duplicate key value");
+    }
+}
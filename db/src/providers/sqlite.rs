@@ -0,0 +1,813 @@
+use alloy::{hex, primitives::U256};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Binary, Bool, Integer, Nullable, Text},
+    upsert::excluded,
+    SqliteConnection,
+};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, sync_connection_wrapper::SyncConnectionWrapper,
+    AsyncConnection, RunQueryDsl,
+};
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::sync::Mutex;
+
+use crate::{
+    from_numeric,
+    schema::{
+        bridge_messages::dsl::{
+            self as bridge_messages_schema_types, bridge_messages as bridge_messages_schema,
+        },
+        chains::dsl::chains as chains_schema,
+    },
+    to_numeric, BlockModel, BridgeDeliveryModel, BridgeSendModel, Chain, DeadLetterModel,
+    DeliveryLatency, FailedBlockModel, RollupVolume, Storage, TimeWindow, Tx, TxFilter,
+    TxIdentifier, TxModel, ROLLUP_RESOLUTIONS,
+};
+
+/// Embedded storage backend on a single SQLite file, for local runs and CI.
+/// SQLite has a single writer, so queries share one connection.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    conn: Arc<Mutex<SyncConnectionWrapper<SqliteConnection>>>,
+}
+
+impl SqliteStorage {
+    pub fn new(conn: SyncConnectionWrapper<SqliteConnection>) -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct BlockRow {
+    #[diesel(sql_type = BigInt)]
+    chain_id: i64,
+    #[diesel(sql_type = BigInt)]
+    block_number: i64,
+    #[diesel(sql_type = Text)]
+    block_hash: String,
+    #[diesel(sql_type = BigInt)]
+    block_timestamp: i64,
+    #[diesel(sql_type = BigInt)]
+    tx_count: i64,
+    #[diesel(sql_type = Text)]
+    counts: String,
+    #[diesel(sql_type = Text)]
+    bridged: String,
+}
+
+impl From<BlockRow> for BlockModel {
+    fn from(row: BlockRow) -> Self {
+        Self {
+            chain_id: row.chain_id,
+            block_number: row.block_number,
+            block_hash: row.block_hash,
+            block_timestamp: row.block_timestamp,
+            tx_count: row.tx_count,
+            counts: serde_json::from_str(&row.counts).unwrap_or_default(),
+            bridged: serde_json::from_str(&row.bridged).unwrap_or_default(),
+        }
+    }
+}
+
+/// Transaction row with amounts as decimal text.
+#[derive(QueryableByName)]
+struct TransactionRow {
+    #[diesel(sql_type = BigInt)]
+    chain_id: i64,
+    #[diesel(sql_type = Binary)]
+    transaction_hash: Vec<u8>,
+    #[diesel(sql_type = BigInt)]
+    transaction_nonce: i64,
+    #[diesel(sql_type = Nullable<Binary>)]
+    block_hash: Option<Vec<u8>>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    block_number: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    transaction_index: Option<i64>,
+    #[diesel(sql_type = Binary)]
+    _from: Vec<u8>,
+    #[diesel(sql_type = Nullable<Binary>)]
+    _to: Option<Vec<u8>>,
+    #[diesel(sql_type = Text)]
+    value: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    gas_price: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    gas: i64,
+    #[diesel(sql_type = Binary)]
+    input: Vec<u8>,
+    #[diesel(sql_type = Text)]
+    v: String,
+    #[diesel(sql_type = Text)]
+    r: String,
+    #[diesel(sql_type = Text)]
+    s: String,
+    #[diesel(sql_type = Text)]
+    transaction_type: String,
+    #[diesel(sql_type = Bool)]
+    impersonated: bool,
+    #[diesel(sql_type = Nullable<Text>)]
+    max_priority_fee_per_gas: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    max_fee_per_gas: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    tx_type: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    access_list: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    max_fee_per_blob_gas: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    blob_versioned_hashes: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    authorization_list: Option<String>,
+    #[diesel(sql_type = Nullable<Bool>)]
+    y_parity: Option<bool>,
+    #[diesel(sql_type = Nullable<Binary>)]
+    raw_transaction: Option<Vec<u8>>,
+}
+
+fn parse_numeric(value: &str) -> BigDecimal {
+    to_numeric(value.parse::<U256>().unwrap_or_default())
+}
+
+fn parse_json(value: Option<String>) -> Option<serde_json::Value> {
+    value.and_then(|value| serde_json::from_str(&value).ok())
+}
+
+impl From<TransactionRow> for TxModel {
+    fn from(row: TransactionRow) -> Self {
+        Self {
+            chain_id: row.chain_id,
+            transaction_hash: row.transaction_hash,
+            transaction_nonce: row.transaction_nonce,
+            block_hash: row.block_hash,
+            block_number: row.block_number.unwrap_or_default(),
+            transaction_index: row.transaction_index,
+            _from: row._from,
+            _to: row._to,
+            value: parse_numeric(&row.value),
+            gas_price: row.gas_price.as_deref().map(parse_numeric),
+            gas: row.gas,
+            input: row.input,
+            v: row.v,
+            r: row.r,
+            s: row.s,
+            transaction_type: row.transaction_type,
+            impersonated: row.impersonated,
+            max_priority_fee_per_gas: row.max_priority_fee_per_gas.as_deref().map(parse_numeric),
+            max_fee_per_gas: row.max_fee_per_gas.as_deref().map(parse_numeric),
+            tx_type: row.tx_type.unwrap_or_default(),
+            access_list: parse_json(row.access_list),
+            max_fee_per_blob_gas: row.max_fee_per_blob_gas.as_deref().map(parse_numeric),
+            blob_versioned_hashes: parse_json(row.blob_versioned_hashes),
+            authorization_list: parse_json(row.authorization_list),
+            y_parity: row.y_parity,
+            raw_transaction: row.raw_transaction,
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct RollupValue {
+    #[diesel(sql_type = Text)]
+    value: String,
+}
+
+#[derive(QueryableByName)]
+struct LatencyRow {
+    #[diesel(sql_type = BigInt)]
+    source_chain_id: i64,
+    #[diesel(sql_type = BigInt)]
+    destination_chain_id: i64,
+    #[diesel(sql_type = BigInt)]
+    latency_secs: i64,
+}
+
+/// Continuous percentile of ascending `latencies`, interpolated like
+/// Postgres' `percentile_cont`.
+fn percentile_cont(latencies: &[f64], fraction: f64) -> f64 {
+    let position = fraction * (latencies.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+
+    latencies[lower] + (latencies[upper] - latencies[lower]) * (position - lower as f64)
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn get_txs(
+        &self,
+        identifier: &TxIdentifier,
+        filter: &TxFilter,
+        tx_type: Option<Tx>,
+        limit: u64,
+    ) -> Result<Vec<TxModel>, std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        let tx_hash = identifier
+            .tx_hash
+            .as_ref()
+            .map(hex::decode)
+            .transpose()
+            .map_err(|_| std::io::ErrorKind::InvalidInput)?;
+        let tx_type = tx_type
+            .filter(|tx_type| *tx_type != Tx::All)
+            .map(|tx_type| tx_type.to_string());
+        let chain_id = filter.chain_id.map(|id| id as i64);
+
+        let rows = diesel::sql_query(
+            "SELECT chain_id, transaction_hash, transaction_nonce, block_hash, block_number, \
+                transaction_index, _from, _to, value, gas_price, gas, input, v, r, s, \
+                transaction_type, impersonated, max_priority_fee_per_gas, max_fee_per_gas, tx_type, \
+                access_list, max_fee_per_blob_gas, blob_versioned_hashes, authorization_list, \
+                y_parity, raw_transaction \
+            FROM transactions \
+            WHERE (? IS NULL OR chain_id = ?) AND (? IS NULL OR transaction_hash = ?) \
+                AND (? IS NULL OR tx_type = ?) \
+            ORDER BY block_number DESC \
+            LIMIT ? OFFSET ?",
+        )
+        .bind::<Nullable<BigInt>, _>(chain_id)
+        .bind::<Nullable<BigInt>, _>(chain_id)
+        .bind::<Nullable<Binary>, _>(tx_hash.clone())
+        .bind::<Nullable<Binary>, _>(tx_hash)
+        .bind::<Nullable<Text>, _>(tx_type.clone())
+        .bind::<Nullable<Text>, _>(tx_type)
+        .bind::<BigInt, _>(limit as i64)
+        .bind::<BigInt, _>((identifier.page_idx.unwrap_or(0) * limit) as i64)
+        .load::<TransactionRow>(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn add_txns(&self, chain: Chain, txns: Vec<TxModel>) -> Result<(), std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                diesel::insert_into(chains_schema)
+                    .values(&chain)
+                    .on_conflict(crate::schema::chains::chain_id)
+                    .do_update()
+                    .set(crate::schema::chains::latest_tps.eq(chain.latest_tps))
+                    .execute(conn)
+                    .await?;
+
+                for txn in txns {
+                    diesel::sql_query(
+                        "INSERT INTO transactions \
+                            (chain_id, transaction_hash, transaction_nonce, block_hash, \
+                            block_number, transaction_index, _from, _to, value, gas_price, gas, \
+                            input, v, r, s, transaction_type, impersonated, \
+                            max_priority_fee_per_gas, max_fee_per_gas, tx_type, access_list, \
+                            max_fee_per_blob_gas, blob_versioned_hashes, authorization_list, \
+                            y_parity, raw_transaction) \
+                        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, \
+                            ?, ?, ?, ?, ?, ?) \
+                        ON CONFLICT (chain_id, transaction_hash) DO NOTHING",
+                    )
+                    .bind::<BigInt, _>(txn.chain_id)
+                    .bind::<Binary, _>(txn.transaction_hash)
+                    .bind::<BigInt, _>(txn.transaction_nonce)
+                    .bind::<Nullable<Binary>, _>(txn.block_hash)
+                    .bind::<BigInt, _>(txn.block_number)
+                    .bind::<Nullable<BigInt>, _>(txn.transaction_index)
+                    .bind::<Binary, _>(txn._from)
+                    .bind::<Nullable<Binary>, _>(txn._to)
+                    .bind::<Text, _>(from_numeric(&txn.value).to_string())
+                    .bind::<Nullable<Text>, _>(txn.gas_price.map(|x| from_numeric(&x).to_string()))
+                    .bind::<BigInt, _>(txn.gas)
+                    .bind::<Binary, _>(txn.input)
+                    .bind::<Text, _>(txn.v)
+                    .bind::<Text, _>(txn.r)
+                    .bind::<Text, _>(txn.s)
+                    .bind::<Text, _>(txn.transaction_type)
+                    .bind::<Bool, _>(txn.impersonated)
+                    .bind::<Nullable<Text>, _>(
+                        txn.max_priority_fee_per_gas
+                            .map(|x| from_numeric(&x).to_string()),
+                    )
+                    .bind::<Nullable<Text>, _>(
+                        txn.max_fee_per_gas.map(|x| from_numeric(&x).to_string()),
+                    )
+                    .bind::<Text, _>(txn.tx_type)
+                    .bind::<Nullable<Text>, _>(txn.access_list.map(|x| x.to_string()))
+                    .bind::<Nullable<Text>, _>(
+                        txn.max_fee_per_blob_gas
+                            .map(|x| from_numeric(&x).to_string()),
+                    )
+                    .bind::<Nullable<Text>, _>(txn.blob_versioned_hashes.map(|x| x.to_string()))
+                    .bind::<Nullable<Text>, _>(txn.authorization_list.map(|x| x.to_string()))
+                    .bind::<Nullable<Bool>, _>(txn.y_parity)
+                    .bind::<Nullable<Binary>, _>(txn.raw_transaction)
+                    .execute(conn)
+                    .await?;
+                }
+
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn add_bridge_events(
+        &self,
+        sends: Vec<BridgeSendModel>,
+        deliveries: Vec<BridgeDeliveryModel>,
+    ) -> Result<(), std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                for send in &sends {
+                    diesel::insert_into(bridge_messages_schema)
+                        .values(send)
+                        .on_conflict((
+                            bridge_messages_schema_types::source_chain_id,
+                            bridge_messages_schema_types::destination_chain_id,
+                            bridge_messages_schema_types::message_id,
+                        ))
+                        .do_update()
+                        .set((
+                            bridge_messages_schema_types::sent_block
+                                .eq(excluded(bridge_messages_schema_types::sent_block)),
+                            bridge_messages_schema_types::sent_at
+                                .eq(excluded(bridge_messages_schema_types::sent_at)),
+                        ))
+                        .execute(conn)
+                        .await?;
+                }
+
+                for delivery in &deliveries {
+                    diesel::insert_into(bridge_messages_schema)
+                        .values(delivery)
+                        .on_conflict((
+                            bridge_messages_schema_types::source_chain_id,
+                            bridge_messages_schema_types::destination_chain_id,
+                            bridge_messages_schema_types::message_id,
+                        ))
+                        .do_update()
+                        .set((
+                            bridge_messages_schema_types::delivered_block
+                                .eq(excluded(bridge_messages_schema_types::delivered_block)),
+                            bridge_messages_schema_types::delivered_at
+                                .eq(excluded(bridge_messages_schema_types::delivered_at)),
+                        ))
+                        .execute(conn)
+                        .await?;
+                }
+
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn match_bridge_messages(&self) -> Result<usize, std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        let matched = diesel::update(
+            bridge_messages_schema
+                .filter(bridge_messages_schema_types::latency_secs.is_null())
+                .filter(bridge_messages_schema_types::sent_at.is_not_null())
+                .filter(bridge_messages_schema_types::delivered_at.is_not_null()),
+        )
+        .set(
+            bridge_messages_schema_types::latency_secs
+                .eq(bridge_messages_schema_types::delivered_at
+                    - bridge_messages_schema_types::sent_at),
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(matched)
+    }
+
+    async fn add_block_record(&self, block: BlockModel) -> Result<(), std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                let previous = diesel::sql_query(
+                    "SELECT chain_id, block_number, block_hash, block_timestamp, tx_count, counts, bridged \
+                    FROM blocks \
+                    WHERE chain_id = ? AND block_number = ?",
+                )
+                .bind::<BigInt, _>(block.chain_id)
+                .bind::<BigInt, _>(block.block_number)
+                .get_result::<BlockRow>(conn)
+                .await
+                .optional()?
+                .map(BlockModel::from);
+                if previous.as_ref() == Some(&block) {
+                    return Ok(());
+                }
+
+                diesel::sql_query(
+                    "INSERT INTO blocks \
+                        (chain_id, block_number, block_hash, block_timestamp, tx_count, counts, bridged) \
+                    VALUES (?, ?, ?, ?, ?, ?, ?) \
+                    ON CONFLICT (chain_id, block_number) DO UPDATE SET \
+                        block_hash = excluded.block_hash, \
+                        block_timestamp = excluded.block_timestamp, \
+                        tx_count = excluded.tx_count, \
+                        counts = excluded.counts, \
+                        bridged = excluded.bridged",
+                )
+                .bind::<BigInt, _>(block.chain_id)
+                .bind::<BigInt, _>(block.block_number)
+                .bind::<Text, _>(&block.block_hash)
+                .bind::<BigInt, _>(block.block_timestamp)
+                .bind::<BigInt, _>(block.tx_count)
+                .bind::<Text, _>(block.counts.to_string())
+                .bind::<Text, _>(block.bridged.to_string())
+                .execute(conn)
+                .await?;
+
+                if let Some(previous) = previous {
+                    add_rollups(conn, &previous, false).await?;
+                }
+                add_rollups(conn, &block, true).await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn get_block_records(
+        &self,
+        chain_id: Option<u64>,
+        from: i64,
+        to: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<BlockModel>, std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        let rows = diesel::sql_query(
+            "SELECT chain_id, block_number, block_hash, block_timestamp, tx_count, counts, bridged \
+            FROM blocks \
+            WHERE block_timestamp > ? AND block_timestamp <= ? AND (? IS NULL OR chain_id = ?) \
+            ORDER BY chain_id, block_number \
+            LIMIT ? OFFSET ?",
+        )
+        .bind::<BigInt, _>(from)
+        .bind::<BigInt, _>(to)
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .bind::<BigInt, _>(limit)
+        .bind::<BigInt, _>(offset)
+        .load::<BlockRow>(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(rows.into_iter().map(BlockModel::from).collect())
+    }
+
+    async fn rollup_volume(
+        &self,
+        chain_id: Option<u64>,
+        tx_type: Option<Tx>,
+        resolution: i64,
+        window: &TimeWindow,
+    ) -> Result<Vec<RollupVolume>, std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        diesel::sql_query(
+            "SELECT (bucket_start - ?) / ? AS idx, SUM(tx_count) AS tx_count, \
+                SUM(successful) AS successful, SUM(failed) AS failed \
+            FROM metric_rollups \
+            WHERE bucket_secs = ? AND tx_type = ? AND bucket_start >= ? AND bucket_start < ? \
+                AND (? IS NULL OR chain_id = ?) \
+            GROUP BY idx",
+        )
+        .bind::<BigInt, _>(window.from)
+        .bind::<BigInt, _>(window.bucket)
+        .bind::<BigInt, _>(resolution)
+        .bind::<Text, _>(tx_type.unwrap_or(Tx::All).to_string())
+        .bind::<BigInt, _>(window.from)
+        .bind::<BigInt, _>(window.to)
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .load::<RollupVolume>(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted.into())
+    }
+
+    /// SQLite has no `percentile_cont`, so the percentiles are computed from
+    /// the latencies of the matching messages.
+    async fn delivery_latency(
+        &self,
+        chain_id: Option<u64>,
+        source_chain_id: Option<u64>,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<DeliveryLatency>, std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        let rows = diesel::sql_query(
+            "SELECT source_chain_id, destination_chain_id, latency_secs \
+            FROM bridge_messages \
+            WHERE latency_secs IS NOT NULL AND delivered_at > ? AND delivered_at <= ? \
+                AND (? IS NULL OR destination_chain_id = ?) \
+                AND (? IS NULL OR source_chain_id = ?)",
+        )
+        .bind::<BigInt, _>(from)
+        .bind::<BigInt, _>(to)
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .bind::<Nullable<BigInt>, _>(chain_id.map(|id| id as i64))
+        .bind::<Nullable<BigInt>, _>(source_chain_id.map(|id| id as i64))
+        .bind::<Nullable<BigInt>, _>(source_chain_id.map(|id| id as i64))
+        .load::<LatencyRow>(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        let mut latencies: BTreeMap<(i64, i64), Vec<f64>> = BTreeMap::new();
+        for row in rows {
+            latencies
+                .entry((row.source_chain_id, row.destination_chain_id))
+                .or_default()
+                .push(row.latency_secs as f64);
+        }
+
+        Ok(latencies
+            .into_iter()
+            .map(|((source_chain_id, destination_chain_id), mut latencies)| {
+                latencies.sort_by(f64::total_cmp);
+                DeliveryLatency {
+                    source_chain_id,
+                    destination_chain_id,
+                    messages: latencies.len() as i64,
+                    p50: percentile_cont(&latencies, 0.5),
+                    p90: percentile_cont(&latencies, 0.9),
+                    p99: percentile_cont(&latencies, 0.99),
+                    max: latencies.last().copied().unwrap_or_default() as i64,
+                }
+            })
+            .collect())
+    }
+
+    async fn add_dead_letter(&self, letter: DeadLetterModel) -> Result<(), std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        diesel::sql_query(
+            "INSERT INTO dead_letters (kind, payload, error, attempts) VALUES (?, ?, ?, ?)",
+        )
+        .bind::<Text, _>(letter.kind)
+        .bind::<Text, _>(letter.payload.to_string())
+        .bind::<Text, _>(letter.error)
+        .bind::<Integer, _>(letter.attempts)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn record_block_failure(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+        error: String,
+        failed_at: i64,
+    ) -> Result<FailedBlockModel, std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        diesel::sql_query(
+            "INSERT INTO failed_blocks (chain_id, block_number, error, failed_at) \
+            VALUES (?, ?, ?, ?) \
+            ON CONFLICT (chain_id, block_number) DO UPDATE SET \
+                error = excluded.error, \
+                attempts = failed_blocks.attempts + 1, \
+                failed_at = excluded.failed_at \
+            RETURNING chain_id, block_number, error, attempts, skipped, failed_at",
+        )
+        .bind::<BigInt, _>(chain_id as i64)
+        .bind::<BigInt, _>(block_number as i64)
+        .bind::<Text, _>(error)
+        .bind::<BigInt, _>(failed_at)
+        .get_result::<FailedBlockModel>(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted.into())
+    }
+
+    async fn skip_failed_block(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+    ) -> Result<(), std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        diesel::sql_query(
+            "UPDATE failed_blocks SET skipped = TRUE WHERE chain_id = ? AND block_number = ?",
+        )
+        .bind::<BigInt, _>(chain_id as i64)
+        .bind::<BigInt, _>(block_number as i64)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn remove_failed_block(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+    ) -> Result<(), std::io::Error> {
+        let mut conn = self.conn.lock().await;
+
+        diesel::sql_query("DELETE FROM failed_blocks WHERE chain_id = ? AND block_number = ?")
+            .bind::<BigInt, _>(chain_id as i64)
+            .bind::<BigInt, _>(block_number as i64)
+            .execute(&mut *conn)
+            .await
+            .map_err(|_| std::io::ErrorKind::ConnectionAborted)?;
+
+        Ok(())
+    }
+
+    async fn get_failed_blocks(
+        &self,
+        chain_id: Option<u64>,
+    ) -> Result<Vec<FailedBlockModel>, std::io::Error> {
+        let mut conn = self.conn.lock().await;
+        let chain_id = chain_id.map(|id| id as i64);
+
+        diesel::sql_query(
+            "SELECT chain_id, block_number, error, attempts, skipped, failed_at \
+            FROM failed_blocks \
+            WHERE (? IS NULL OR chain_id = ?) \
+            ORDER BY chain_id, block_number",
+        )
+        .bind::<Nullable<BigInt>, _>(chain_id)
+        .bind::<Nullable<BigInt>, _>(chain_id)
+        .load::<FailedBlockModel>(&mut *conn)
+        .await
+        .map_err(|_| std::io::ErrorKind::ConnectionAborted.into())
+    }
+}
+
+/// Adds the rollup rows of `block` to the buckets of every width in
+/// `ROLLUP_RESOLUTIONS` holding the block, or takes them away when `add` is
+/// false. The value column is decimal text, so sums are taken here.
+async fn add_rollups(
+    conn: &mut SyncConnectionWrapper<SqliteConnection>,
+    block: &BlockModel,
+    add: bool,
+) -> Result<(), diesel::result::Error> {
+    let sign = if add { 1 } else { -1 };
+    let timestamp = block.block_timestamp;
+
+    for bucket_secs in ROLLUP_RESOLUTIONS {
+        let bucket_start = timestamp - timestamp.rem_euclid(bucket_secs);
+        for (tx, counts) in block.rollup_rows() {
+            let previous = diesel::sql_query(
+                "SELECT value FROM metric_rollups \
+                WHERE chain_id = ? AND bucket_secs = ? AND tx_type = ? \
+                    AND bucket_start = ?",
+            )
+            .bind::<BigInt, _>(block.chain_id)
+            .bind::<BigInt, _>(bucket_secs)
+            .bind::<Text, _>(tx.to_string())
+            .bind::<BigInt, _>(bucket_start)
+            .get_result::<RollupValue>(conn)
+            .await
+            .optional()?
+            .and_then(|row| row.value.parse::<U256>().ok())
+            .unwrap_or(U256::ZERO);
+            let value = if add {
+                previous.saturating_add(counts.value)
+            } else {
+                previous.saturating_sub(counts.value)
+            };
+
+            diesel::sql_query(
+                "INSERT INTO metric_rollups \
+                    (chain_id, bucket_secs, bucket_start, tx_type, tx_count, \
+                    successful, failed, gas_used, value) \
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?) \
+                ON CONFLICT (chain_id, bucket_secs, tx_type, bucket_start) DO UPDATE SET \
+                    tx_count = metric_rollups.tx_count + excluded.tx_count, \
+                    successful = metric_rollups.successful + excluded.successful, \
+                    failed = metric_rollups.failed + excluded.failed, \
+                    gas_used = metric_rollups.gas_used + excluded.gas_used, \
+                    value = excluded.value",
+            )
+            .bind::<BigInt, _>(block.chain_id)
+            .bind::<BigInt, _>(bucket_secs)
+            .bind::<BigInt, _>(bucket_start)
+            .bind::<Text, _>(tx.to_string())
+            .bind::<BigInt, _>(sign * counts.total as i64)
+            .bind::<BigInt, _>(sign * counts.successful() as i64)
+            .bind::<BigInt, _>(sign * counts.failed as i64)
+            .bind::<BigInt, _>(sign * counts.gas_used as i64)
+            .bind::<Text, _>(value.to_string())
+            .execute(conn)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{connections::SQLITE_MIGRATIONS, TxCounts};
+    use diesel::Connection;
+    use diesel_migrations::MigrationHarness;
+
+    fn storage() -> SqliteStorage {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        conn.run_pending_migrations(SQLITE_MIGRATIONS).unwrap();
+        SqliteStorage::new(SyncConnectionWrapper::new(conn))
+    }
+
+    fn block(total: u64, failed: u64) -> BlockModel {
+        let counts = TxCounts {
+            total,
+            failed,
+            gas_used: 21_000 * total,
+            value: U256::from(10),
+        };
+        BlockModel::new(
+            1,
+            100,
+            "0x01".to_string(),
+            1_700_000_000,
+            total as usize,
+            &BTreeMap::from([(Tx::Native, counts)]),
+            &BTreeMap::new(),
+        )
+    }
+
+    async fn hourly_volume(storage: &SqliteStorage, tx_type: Option<Tx>) -> Vec<(i64, i64, i64)> {
+        let window = TimeWindow {
+            from: 1_699_999_200,
+            to: 1_700_002_800,
+            bucket: 3600,
+        };
+        storage
+            .rollup_volume(Some(1), tx_type, 3600, &window)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.tx_count, row.successful, row.failed))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn replayed_blocks_are_counted_once() {
+        let storage = storage();
+
+        storage.add_block_record(block(3, 1)).await.unwrap();
+        storage.add_block_record(block(3, 1)).await.unwrap();
+
+        assert_eq!(
+            hourly_volume(&storage, Some(Tx::All)).await,
+            vec![(3, 2, 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn rewritten_blocks_replace_their_counts() {
+        let storage = storage();
+
+        storage.add_block_record(block(3, 1)).await.unwrap();
+        storage.add_block_record(block(5, 0)).await.unwrap();
+
+        assert_eq!(
+            hourly_volume(&storage, Some(Tx::All)).await,
+            vec![(5, 5, 0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn volume_without_a_tx_type_is_the_total() {
+        let storage = storage();
+
+        storage.add_block_record(block(3, 1)).await.unwrap();
+
+        assert_eq!(hourly_volume(&storage, None).await, vec![(3, 2, 1)]);
+    }
+}
@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+
+use crate::{
+    BlockModel, BridgeDeliveryModel, BridgeSendModel, Chain, DeadLetterModel, DeliveryLatency,
+    FailedBlockModel, RollupVolume, TimeWindow, Tx, TxFilter, TxIdentifier, TxModel,
+};
+
+/// Rollup bucket widths in seconds, finest first.
+pub const ROLLUP_RESOLUTIONS: [i64; 3] = [60, 3600, 86400];
+
+/// Persistence of transactions, blocks, rollups and bridge messages. Backends
+/// run the queries in their own SQL dialect and return the same rows.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Page of the transactions matching `identifier` and `filter`, latest
+    /// block first.
+    async fn get_txs(
+        &self,
+        identifier: &TxIdentifier,
+        filter: &TxFilter,
+        tx_type: Option<Tx>,
+        limit: u64,
+    ) -> Result<Vec<TxModel>, std::io::Error>;
+
+    /// Upserts the chain, then inserts the transactions that are not stored
+    /// yet.
+    async fn add_txns(&self, chain: Chain, txns: Vec<TxModel>) -> Result<(), std::io::Error>;
+
+    /// Records the send and delivery side of bridge messages, keeping the
+    /// other side of messages already stored.
+    async fn add_bridge_events(
+        &self,
+        sends: Vec<BridgeSendModel>,
+        deliveries: Vec<BridgeDeliveryModel>,
+    ) -> Result<(), std::io::Error>;
+
+    /// Fills in the latency of every message whose send and delivery have both
+    /// been indexed, returning the number of newly matched messages.
+    async fn match_bridge_messages(&self) -> Result<usize, std::io::Error>;
+
+    /// Stores a block and moves its counts into the rollups of every width in
+    /// `ROLLUP_RESOLUTIONS`. A block stored again replaces the counts of its
+    /// previous record, so replays leave the rollups unchanged.
+    async fn add_block_record(&self, block: BlockModel) -> Result<(), std::io::Error>;
+
+    /// Blocks with a timestamp in `(from, to]`, ordered by chain and height.
+    async fn get_block_records(
+        &self,
+        chain_id: Option<u64>,
+        from: i64,
+        to: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<BlockModel>, std::io::Error>;
+
+    /// Sums of the `resolution` rollups per bucket of `window`, keyed by the
+    /// bucket index. Empty buckets are omitted.
+    async fn rollup_volume(
+        &self,
+        chain_id: Option<u64>,
+        tx_type: Option<Tx>,
+        resolution: i64,
+        window: &TimeWindow,
+    ) -> Result<Vec<RollupVolume>, std::io::Error>;
+
+    /// Latency percentiles per source and destination chain of the messages
+    /// delivered in `(from, to]`.
+    async fn delivery_latency(
+        &self,
+        chain_id: Option<u64>,
+        source_chain_id: Option<u64>,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<DeliveryLatency>, std::io::Error>;
+
+    /// Records a write that failed after every retry of the write queue.
+    async fn add_dead_letter(&self, letter: DeadLetterModel) -> Result<(), std::io::Error>;
+
+    /// Records a failed attempt at processing a block, at unix second
+    /// `failed_at`, and returns the block with its total number of attempts.
+    async fn record_block_failure(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+        error: String,
+        failed_at: i64,
+    ) -> Result<FailedBlockModel, std::io::Error>;
+
+    /// Marks a failed block as given up on, until it is retried manually.
+    async fn skip_failed_block(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+    ) -> Result<(), std::io::Error>;
+
+    /// Forgets a failed block once it has been processed.
+    async fn remove_failed_block(
+        &self,
+        chain_id: u64,
+        block_number: u64,
+    ) -> Result<(), std::io::Error>;
+
+    /// Failed blocks of one chain, or all chains, ordered by chain and height.
+    async fn get_failed_blocks(
+        &self,
+        chain_id: Option<u64>,
+    ) -> Result<Vec<FailedBlockModel>, std::io::Error>;
+}
@@ -0,0 +1,512 @@
+use alloy::primitives::U256;
+use async_trait::async_trait;
+use redis::RedisResult;
+use std::collections::BTreeMap;
+
+use crate::{
+    bridged_from_field, tps_series, BlockCounts, BlockModel, BlockSample, CacheUsage, TimeWindow,
+    TpsResponse, Tx, TxCounts, Type, BRIDGED_IN_FIELD,
+};
+
+/// Storage of the per-block metric series. Backends implement the block-level
+/// reads and writes; the metrics are derived from them by the provided
+/// methods, so every backend answers queries the same way.
+#[async_trait]
+pub trait MetricsStore: Send + Sync {
+    /// Writes the counts and bridged value of many blocks. The latest height
+    /// and timestamp of a chain never move backwards.
+    async fn add_blocks(&self, blocks: &[BlockCounts]) -> RedisResult<()>;
+
+    /// Rewrites blocks, including their bridged value, from their persisted
+    /// records.
+    async fn restore_blocks(&self, blocks: &[BlockModel]) -> RedisResult<()>;
+
+    /// Ids of the chains with at least one block.
+    async fn get_chains(&self) -> RedisResult<Vec<u64>>;
+
+    async fn get_latest_height(&self, chain_id: &u64) -> RedisResult<u64>;
+
+    /// Timestamp of the latest block of a chain, as indexed.
+    async fn get_latest_timestamp(&self, chain_id: &u64) -> RedisResult<i64>;
+
+    /// Last `window + 1` block samples of a chain, oldest first.
+    async fn get_block_samples(&self, chain_id: &u64, window: u64)
+        -> RedisResult<Vec<BlockSample>>;
+
+    /// Block samples with a timestamp in `(from_ms, to_ms]`, oldest first.
+    async fn get_block_samples_in_range(
+        &self,
+        chain_id: &u64,
+        from_ms: i64,
+        to_ms: i64,
+    ) -> RedisResult<Vec<BlockSample>>;
+
+    /// Values of a block field over the blocks of a chain with a timestamp in
+    /// `(from_ms, to_ms]`, plus the rollups starting in that range. Blocks
+    /// without the field are skipped.
+    async fn get_block_field_in_range(
+        &self,
+        chain_id: &u64,
+        field: &str,
+        from_ms: i64,
+        to_ms: i64,
+    ) -> RedisResult<Vec<String>>;
+
+    /// Merges the blocks of a chain older than the raw retention into rollup
+    /// buckets, and drops expired rollups. Returns the number of trimmed
+    /// blocks.
+    async fn trim_chain(&self, chain_id: &u64, now: i64) -> RedisResult<usize>;
+
+    async fn get_cache_usage(&self, chain_id: &u64) -> RedisResult<CacheUsage>;
+
+    async fn add_block(
+        &self,
+        chain_id: &u64,
+        timestamp: i64,
+        category_counts: &BTreeMap<Tx, TxCounts>,
+        received_by_source: &BTreeMap<u64, U256>,
+        tx_count: u64,
+        height: u64,
+    ) -> RedisResult<()> {
+        let block = BlockCounts {
+            chain_id: *chain_id,
+            height,
+            timestamp,
+            tx_count,
+            category_counts: category_counts.clone(),
+            received_by_source: received_by_source.clone(),
+        };
+
+        self.add_blocks(&[block]).await
+    }
+
+    async fn get_all_chains_latest_timestamp(&self) -> RedisResult<i64> {
+        let chain_ids = self.get_chains().await?;
+        let mut latest_timestamp = None;
+        for chain_id in chain_ids {
+            if let Ok(timestamp) = self.get_latest_timestamp(&chain_id).await {
+                latest_timestamp = latest_timestamp.max(Some(timestamp));
+            }
+        }
+
+        latest_timestamp.ok_or_else(|| (redis::ErrorKind::TypeError, "no indexed chains").into())
+    }
+
+    async fn get_live_tps(
+        &self,
+        chain_id: &u64,
+        window: &TimeWindow,
+        tx_type: Type,
+    ) -> RedisResult<Vec<(f64, i64)>> {
+        let tx_type = match tx_type.tx_type {
+            Some(tx_type) => tx_type,
+            None => return Ok(Vec::new()),
+        };
+
+        let samples = self
+            .get_block_samples_in_range(chain_id, window.from * 1000, window.to * 1000)
+            .await?;
+
+        let tps_pairs: Vec<(f64, i64)> = tps_series(&samples, &tx_type)
+            .into_iter()
+            .map(|(timestamp_ms, tps)| (tps, timestamp_ms))
+            .collect();
+
+        Ok(tps_pairs)
+    }
+
+    /// Live TPS across all chains on the buckets of `window`. Each chain
+    /// contributes the transactions of the blocks whose timestamp falls in a
+    /// bucket, and buckets without blocks are reported as zero.
+    async fn get_all_chains_live_tps_in_range(
+        &self,
+        window: &TimeWindow,
+        tx_type: Type,
+    ) -> RedisResult<Vec<(f64, i64)>> {
+        let tx_type = match tx_type.tx_type {
+            Some(tx_type) => tx_type,
+            None => return Ok(Vec::new()),
+        };
+
+        let chain_ids = self.get_chains().await?;
+        let buckets = window.buckets();
+        let mut counts = vec![0u64; buckets.len()];
+        for chain_id in &chain_ids {
+            let samples = self
+                .get_block_samples_in_range(chain_id, window.from * 1000, window.to * 1000)
+                .await?;
+            for sample in samples {
+                let idx = (sample.timestamp_ms - window.from * 1000 - 1) / (window.bucket * 1000);
+                if let Some(count) = counts.get_mut(idx as usize) {
+                    *count += sample.count(&tx_type);
+                }
+            }
+        }
+
+        Ok(buckets
+            .iter()
+            .zip(counts)
+            .map(|((start, end), count)| (count as f64 / (end - start) as f64, start * 1000))
+            .collect())
+    }
+
+    async fn get_latest_tps(
+        &self,
+        chain_id: &u64,
+        window: u64,
+        tx_type: Type,
+    ) -> RedisResult<TpsResponse> {
+        let tx_type = match tx_type.tx_type {
+            Some(tx_type) => tx_type,
+            None => return Ok(TpsResponse::default()),
+        };
+
+        let samples = self.get_block_samples(chain_id, window).await?;
+
+        Ok(TpsResponse::from_samples(&samples, &tx_type))
+    }
+
+    async fn get_successful_xfers_in_range(
+        &self,
+        chain_id: &u64,
+        stride: i64,
+        latest_timestamp: i64,
+        tx_type: Type,
+    ) -> RedisResult<u64> {
+        match tx_type.tx_type {
+            Some(tx_type) => {
+                self.get_xfers_in_range(
+                    chain_id,
+                    &tx_type.successful_field(),
+                    stride,
+                    latest_timestamp,
+                )
+                .await
+            }
+            None => Ok(0),
+        }
+    }
+
+    async fn get_total_xfers_in_range(
+        &self,
+        chain_id: &u64,
+        stride: i64,
+        latest_timestamp: i64,
+        tx_type: Type,
+    ) -> RedisResult<u64> {
+        match tx_type.tx_type {
+            Some(tx_type) => {
+                self.get_xfers_in_range(chain_id, &tx_type.total_field(), stride, latest_timestamp)
+                    .await
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Sums a count field over the blocks in `(latest_timestamp - stride,
+    /// latest_timestamp]`, both in unix seconds.
+    async fn get_xfers_in_range(
+        &self,
+        chain_id: &u64,
+        field: &str,
+        stride: i64,
+        latest_timestamp: i64,
+    ) -> RedisResult<u64> {
+        let values = self
+            .get_block_field_in_range(
+                chain_id,
+                field,
+                latest_timestamp.saturating_sub(stride) * 1000,
+                latest_timestamp * 1000,
+            )
+            .await?;
+
+        let sum: u64 = values
+            .iter()
+            .map(|value| value.parse::<u64>().unwrap_or(0))
+            .sum();
+
+        Ok(sum)
+    }
+
+    async fn get_all_chains_success_xfers_in_range(
+        &self,
+        stride: i64,
+        latest_timestamp: i64,
+        tx_type: Type,
+    ) -> RedisResult<u64> {
+        let chain_ids = self.get_chains().await?;
+        let mut total_sum = 0u64;
+
+        for chain_id in chain_ids {
+            let chain_sum = self
+                .get_successful_xfers_in_range(&chain_id, stride, latest_timestamp, tx_type.clone())
+                .await?;
+            total_sum += chain_sum;
+        }
+
+        Ok(total_sum)
+    }
+
+    async fn get_all_chains_total_xfers_in_range(
+        &self,
+        stride: i64,
+        latest_timestamp: i64,
+        tx_type: Type,
+    ) -> RedisResult<u64> {
+        let chain_ids = self.get_chains().await?;
+        let mut total_sum = 0u64;
+
+        for chain_id in chain_ids {
+            let chain_sum = self
+                .get_total_xfers_in_range(&chain_id, stride, latest_timestamp, tx_type.clone())
+                .await?;
+            total_sum += chain_sum;
+        }
+
+        Ok(total_sum)
+    }
+
+    /// Sums the per-chain rates. Chains rarely peak at the same instant, so the
+    /// combined `peak` is an upper bound.
+    async fn get_all_chains_tps_in_range(
+        &self,
+        window: u64,
+        tx_type: Type,
+    ) -> RedisResult<TpsResponse> {
+        let chain_ids = self.get_chains().await?;
+        let mut total = TpsResponse::default();
+        for chain_id in chain_ids {
+            let chain_tps = self
+                .get_latest_tps(&chain_id, window, tx_type.clone())
+                .await?;
+            total.instantaneous += chain_tps.instantaneous;
+            total.moving_average += chain_tps.moving_average;
+            total.peak += chain_tps.peak;
+        }
+
+        Ok(total)
+    }
+
+    /// Sums a bridged value field over the blocks of a chain in
+    /// `(latest_timestamp - stride, latest_timestamp]`.
+    async fn get_bridged_value_in_range(
+        &self,
+        chain_id: &u64,
+        field: &str,
+        stride: i64,
+        latest_timestamp: i64,
+    ) -> RedisResult<U256> {
+        let values = self
+            .get_block_field_in_range(
+                chain_id,
+                field,
+                latest_timestamp.saturating_sub(stride) * 1000,
+                latest_timestamp * 1000,
+            )
+            .await?;
+
+        let sum = values
+            .iter()
+            .map(|value| value.parse::<U256>().unwrap_or(U256::ZERO))
+            .fold(U256::ZERO, |total, amount| total.saturating_add(amount));
+
+        Ok(sum)
+    }
+
+    /// Value bridged out of `chain_id`, i.e. delivered from it on any chain.
+    async fn get_bridged_out_in_range(
+        &self,
+        chain_id: &u64,
+        stride: i64,
+        latest_timestamp: i64,
+    ) -> RedisResult<U256> {
+        let chain_ids = self.get_chains().await?;
+        let field = bridged_from_field(chain_id);
+        let mut total_sum = U256::ZERO;
+
+        for destination_chain_id in chain_ids {
+            let chain_sum = self
+                .get_bridged_value_in_range(&destination_chain_id, &field, stride, latest_timestamp)
+                .await?;
+            total_sum = total_sum.saturating_add(chain_sum);
+        }
+
+        Ok(total_sum)
+    }
+
+    async fn get_all_chains_bridged_value_in_range(
+        &self,
+        stride: i64,
+        latest_timestamp: i64,
+    ) -> RedisResult<U256> {
+        let chain_ids = self.get_chains().await?;
+        let mut total_sum = U256::ZERO;
+
+        for chain_id in chain_ids {
+            let chain_sum = self
+                .get_bridged_value_in_range(&chain_id, BRIDGED_IN_FIELD, stride, latest_timestamp)
+                .await?;
+            total_sum = total_sum.saturating_add(chain_sum);
+        }
+
+        Ok(total_sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DatabaseConnections, MemoryStore, RedisStore, Retention};
+
+    const DESTINATION: u64 = 9_000_001;
+    const SOURCE: u64 = 9_000_002;
+    /// Start of an hourly rollup bucket, in unix seconds.
+    const BUCKET_START: i64 = 1_699_999_200;
+
+    fn counts(total: u64, failed: u64) -> TxCounts {
+        TxCounts {
+            total,
+            failed,
+            ..Default::default()
+        }
+    }
+
+    fn all() -> Type {
+        Type {
+            tx_type: Some(Tx::All),
+        }
+    }
+
+    /// Totals, successful counts and bridged value of the first hour.
+    async fn hour_totals(store: &dyn MetricsStore) -> (u64, u64, u64, U256, U256) {
+        let end = BUCKET_START + 3599;
+        let total = store
+            .get_total_xfers_in_range(&DESTINATION, 3600, end, all())
+            .await
+            .unwrap();
+        let successful = store
+            .get_successful_xfers_in_range(&DESTINATION, 3600, end, all())
+            .await
+            .unwrap();
+        let native = store
+            .get_total_xfers_in_range(
+                &DESTINATION,
+                3600,
+                end,
+                Type {
+                    tx_type: Some(Tx::Native),
+                },
+            )
+            .await
+            .unwrap();
+        let received = store
+            .get_bridged_value_in_range(&DESTINATION, BRIDGED_IN_FIELD, 3600, end)
+            .await
+            .unwrap();
+        let sent = store
+            .get_bridged_out_in_range(&SOURCE, 3600, end)
+            .await
+            .unwrap();
+
+        (total, successful, native, received, sent)
+    }
+
+    /// Runs the same writes and queries against a backend; every backend must
+    /// answer them alike.
+    async fn conformance(store: &dyn MetricsStore) {
+        let blocks = [
+            (1, 10, 3, 1, U256::from(100)),
+            (2, 20, 2, 0, U256::ZERO),
+            (3, 30, 5, 2, U256::from(250)),
+        ];
+        for (height, offset, total, failed, bridged) in blocks {
+            let received_by_source = if bridged.is_zero() {
+                BTreeMap::new()
+            } else {
+                BTreeMap::from([(SOURCE, bridged)])
+            };
+            store
+                .add_block(
+                    &DESTINATION,
+                    BUCKET_START + offset,
+                    &BTreeMap::from([(Tx::Native, counts(total, failed))]),
+                    &received_by_source,
+                    total,
+                    height,
+                )
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(store.get_latest_height(&DESTINATION).await.unwrap(), 3);
+        assert_eq!(
+            store.get_latest_timestamp(&DESTINATION).await.unwrap(),
+            BUCKET_START + 30
+        );
+        let samples = store
+            .get_block_samples_in_range(
+                &DESTINATION,
+                BUCKET_START * 1000,
+                (BUCKET_START + 20) * 1000,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            samples
+                .iter()
+                .map(|sample| (sample.height, sample.count(&Tx::Native)))
+                .collect::<Vec<_>>(),
+            vec![(1, 3), (2, 2)]
+        );
+
+        let expected = (10, 7, 10, U256::from(350), U256::from(350));
+        assert_eq!(hour_totals(store).await, expected);
+
+        // every block is past the raw retention, so the hour is rolled up
+        let now = BUCKET_START + Retention::default().raw_secs + 3600;
+        assert_eq!(store.trim_chain(&DESTINATION, now).await.unwrap(), 3);
+        assert!(store
+            .get_block_samples_in_range(&DESTINATION, 0, now * 1000)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(hour_totals(store).await, expected);
+    }
+
+    #[tokio::test]
+    async fn memory_store_conformance() {
+        conformance(&MemoryStore::new(Retention::default())).await;
+    }
+
+    /// Needs a Redis server configured through `REDIS_*`; its keys of the
+    /// test chains are removed first.
+    #[tokio::test]
+    #[ignore]
+    async fn redis_store_conformance() {
+        let mut conn = DatabaseConnections::init_redis().await.unwrap();
+        for chain_id in [DESTINATION, SOURCE] {
+            let keys: Vec<String> = redis::cmd("KEYS")
+                .arg(format!("chain:{{{}}}:*", chain_id))
+                .query_async(&mut conn)
+                .await
+                .unwrap();
+            if !keys.is_empty() {
+                redis::cmd("DEL")
+                    .arg(keys)
+                    .query_async::<()>(&mut conn)
+                    .await
+                    .unwrap();
+            }
+            redis::cmd("SREM")
+                .arg("chains")
+                .arg(chain_id)
+                .query_async::<()>(&mut conn)
+                .await
+                .unwrap();
+        }
+
+        let store = RedisStore::new(conn, Retention::default()).await.unwrap();
+        conformance(&store).await;
+    }
+}
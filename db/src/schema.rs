@@ -1,5 +1,34 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    blocks (chain_id, block_number) {
+        chain_id -> Int8,
+        block_number -> Int8,
+        block_hash -> Text,
+        block_timestamp -> Int8,
+        tx_count -> Int8,
+        counts -> Jsonb,
+        bridged -> Jsonb,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    bridge_messages (source_chain_id, destination_chain_id, message_id) {
+        source_chain_id -> Int8,
+        destination_chain_id -> Int8,
+        message_id -> Int8,
+        sent_block -> Nullable<Int8>,
+        sent_at -> Nullable<Int8>,
+        delivered_block -> Nullable<Int8>,
+        delivered_at -> Nullable<Int8>,
+        latency_secs -> Nullable<Int8>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     chains (chain_id) {
         chain_id -> Int8,
@@ -8,32 +37,86 @@ diesel::table! {
 }
 
 diesel::table! {
-    transactions (chain_id, transaction_hash) {
+    dead_letters (id) {
+        id -> Int8,
+        kind -> Text,
+        payload -> Jsonb,
+        error -> Text,
+        attempts -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    failed_blocks (chain_id, block_number) {
+        chain_id -> Int8,
+        block_number -> Int8,
+        error -> Text,
+        attempts -> Int4,
+        skipped -> Bool,
+        failed_at -> Int8,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    metric_rollups (chain_id, bucket_secs, tx_type, bucket_start) {
+        chain_id -> Int8,
+        bucket_secs -> Int8,
+        bucket_start -> Int8,
+        tx_type -> Text,
+        tx_count -> Int8,
+        successful -> Int8,
+        failed -> Int8,
+        gas_used -> Int8,
+        value -> Numeric,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    transactions (chain_id, block_number, transaction_hash) {
         chain_id -> Int8,
-        transaction_hash -> Text,
-        transaction_nonce -> Text,
-        block_hash -> Nullable<Text>,
-        block_number -> Nullable<Int8>,
+        transaction_hash -> Bytea,
+        transaction_nonce -> Int8,
+        block_hash -> Nullable<Bytea>,
+        block_number -> Int8,
         transaction_index -> Nullable<Int8>,
-        _from -> Text,
-        _to -> Nullable<Text>,
-        value -> Text,
-        gas_price -> Nullable<Text>,
-        gas -> Text,
-        input -> Text,
+        _from -> Bytea,
+        _to -> Nullable<Bytea>,
+        value -> Numeric,
+        gas_price -> Nullable<Numeric>,
+        gas -> Int8,
+        input -> Bytea,
         v -> Text,
         r -> Text,
         s -> Text,
         transaction_type -> Text,
         impersonated -> Bool,
-        max_priority_fee_per_gas -> Nullable<Text>,
-        max_fee_per_gas -> Nullable<Text>,
+        max_priority_fee_per_gas -> Nullable<Numeric>,
+        max_fee_per_gas -> Nullable<Numeric>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
         tx_type -> Text,
+        access_list -> Nullable<Jsonb>,
+        max_fee_per_blob_gas -> Nullable<Numeric>,
+        blob_versioned_hashes -> Nullable<Jsonb>,
+        authorization_list -> Nullable<Jsonb>,
+        y_parity -> Nullable<Bool>,
+        raw_transaction -> Nullable<Bytea>,
     }
 }
 
 diesel::joinable!(transactions -> chains (chain_id));
 
-diesel::allow_tables_to_appear_in_same_query!(chains, transactions,);
+diesel::allow_tables_to_appear_in_same_query!(
+    bridge_messages,
+    chains,
+    dead_letters,
+    failed_blocks,
+    metric_rollups,
+    transactions,
+);
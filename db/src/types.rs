@@ -1,4 +1,8 @@
-use std::fmt;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use alloy::{
     primitives::{
@@ -10,9 +14,11 @@ use alloy::{
     sol_types::SolEvent,
 };
 
-use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
 use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::ConversionError;
+
 #[derive(Clone)]
 pub enum Metric {
     CurrentTPS,
@@ -20,13 +26,380 @@ pub enum Metric {
     TransactionVolume,
     TotalTransactions,
     SuccessfulTransfers,
+    DeliveryLatency,
+    BridgedValue,
+    FailureRate,
+    CacheUsage,
+}
+
+impl Metric {
+    /// Default span and bucket width in seconds when no `from`/`to`/`bucket`
+    /// is given, keeping the historical meaning of `stride`.
+    pub fn default_window(&self, stride: &Stride) -> (i64, i64) {
+        match self {
+            Metric::TransactionVolume | Metric::BridgedValue => match stride.stride {
+                Some(1) => (3600, 30),
+                Some(_) => (600, 10),
+                None => (86400, 3600),
+            },
+            Metric::LiveTPS | Metric::CurrentTPS => match stride.stride {
+                None | Some(1) => (3600, 60),
+                Some(_) => (600, 10),
+            },
+            Metric::DeliveryLatency => match stride.stride {
+                Some(1) => (3600, 3600),
+                Some(_) => (600, 600),
+                None => (86400, 86400),
+            },
+            Metric::TotalTransactions
+            | Metric::SuccessfulTransfers
+            | Metric::FailureRate
+            | Metric::CacheUsage => (86400, 86400),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TxResponse {
     pub successful_txns: u64,
+    pub failed_txns: u64,
+    pub total_txns: u64,
+    pub timestamp: i64,
+    pub time: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct FailureRateResponse {
+    pub successful_txns: u64,
+    pub failed_txns: u64,
     pub total_txns: u64,
-    pub timestamp: String,
+    pub failure_rate: f64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct TpsResponse {
+    pub instantaneous: f64,
+    pub moving_average: f64,
+    pub peak: f64,
+}
+
+impl TpsResponse {
+    /// Computes TPS from consecutive block samples, oldest first.
+    pub fn from_samples(samples: &[BlockSample], tx_type: &Tx) -> Self {
+        let steps = tps_steps(samples, tx_type);
+        let rates = tps_series(samples, tx_type);
+
+        let moving_average = match (steps.first(), steps.last()) {
+            (Some(first), Some(last)) if last.0 > first.0 => {
+                let count: u64 = steps[1..].iter().map(|(_, count)| count).sum();
+                count as f64 * 1000.0 / (last.0 - first.0) as f64
+            }
+            _ => 0.0,
+        };
+
+        Self {
+            instantaneous: rates.last().map(|(_, rate)| *rate).unwrap_or(0.0),
+            moving_average,
+            peak: rates.iter().map(|(_, rate)| *rate).fold(0.0, f64::max),
+        }
+    }
+}
+
+/// Merges blocks that share a timestamp so every rate is taken over a
+/// non-zero elapsed time.
+fn tps_steps(samples: &[BlockSample], tx_type: &Tx) -> Vec<(i64, u64)> {
+    let mut steps: Vec<(i64, u64)> = Vec::new();
+    for sample in samples {
+        match steps.last_mut() {
+            Some((timestamp_ms, count)) if *timestamp_ms == sample.timestamp_ms => {
+                *count += sample.count(tx_type);
+            }
+            _ => steps.push((sample.timestamp_ms, sample.count(tx_type))),
+        }
+    }
+
+    steps
+}
+
+/// TPS of every block (or group of blocks sharing a timestamp) after the
+/// first, keyed by its timestamp in milliseconds.
+pub fn tps_series(samples: &[BlockSample], tx_type: &Tx) -> Vec<(i64, f64)> {
+    tps_steps(samples, tx_type)
+        .windows(2)
+        .map(|pair| {
+            let elapsed_ms = (pair[1].0 - pair[0].0) as f64;
+            (pair[1].0, pair[1].1 as f64 * 1000.0 / elapsed_ms)
+        })
+        .collect()
+}
+
+/// Counts of an indexed block, as written to its `chain:{id}:block:{height}`
+/// hash.
+#[derive(Clone, Debug, Default)]
+pub struct BlockCounts {
+    pub chain_id: u64,
+    pub height: u64,
+    /// Block timestamp in unix seconds or milliseconds.
+    pub timestamp: i64,
+    pub tx_count: u64,
+    pub category_counts: BTreeMap<Tx, TxCounts>,
+    /// Value delivered in the block per source chain.
+    pub received_by_source: BTreeMap<u64, U256>,
+}
+
+impl BlockCounts {
+    /// Total and successful count fields, overall and per category.
+    pub fn fields(&self) -> Vec<(String, u64)> {
+        let failed: u64 = self
+            .category_counts
+            .values()
+            .map(|counts| counts.failed)
+            .sum();
+
+        let mut fields: Vec<(String, u64)> = vec![
+            (Tx::All.total_field(), self.tx_count),
+            (
+                Tx::All.successful_field(),
+                self.tx_count.saturating_sub(failed),
+            ),
+        ];
+        for category in Tx::CATEGORIES.iter() {
+            let counts = self
+                .category_counts
+                .get(category)
+                .copied()
+                .unwrap_or_default();
+            fields.push((category.total_field(), counts.total));
+            fields.push((category.successful_field(), counts.successful()));
+        }
+
+        fields
+    }
+}
+
+/// Transaction counts of a single block, read from its
+/// `chain:{id}:block:{height}` hash.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BlockSample {
+    pub height: u64,
+    pub timestamp_ms: i64,
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl BlockSample {
+    pub fn count(&self, tx_type: &Tx) -> u64 {
+        self.counts.get(&tx_type.to_string()).copied().unwrap_or(0)
+    }
+
+    /// Builds a sample from the fields of a block hash, taking the counts
+    /// from its `total:{category}` fields.
+    pub fn from_fields(fields: &BTreeMap<String, String>) -> Option<Self> {
+        let counts = fields
+            .iter()
+            .filter_map(|(field, value)| {
+                let category = field.strip_prefix("total:")?;
+                Some((category.to_string(), value.parse().ok()?))
+            })
+            .collect();
+
+        Some(Self {
+            height: fields.get("height")?.parse().ok()?,
+            timestamp_ms: fields.get("timestamp_ms")?.parse().ok()?,
+            counts,
+        })
+    }
+}
+
+/// Per-block transfer counts of a single `Tx` category, with the gas used
+/// and value moved by its transactions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxCounts {
+    pub total: u64,
+    pub failed: u64,
+    pub gas_used: u64,
+    pub value: U256,
+}
+
+impl TxCounts {
+    pub fn successful(&self) -> u64 {
+        self.total.saturating_sub(self.failed)
+    }
+}
+
+/// Database persisting transactions, blocks, rollups and bridge messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Postgres,
+    /// Single file at `DATABASE_URL`, for local runs and CI.
+    Sqlite,
+}
+
+/// Backend holding the metric series served by the API.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsBackend {
+    #[default]
+    Redis,
+    /// Process memory, lost on restart.
+    Memory,
+}
+
+/// How long the metrics store keeps metric series. Blocks older than `raw_secs` are
+/// merged into `rollup_bucket_secs` rollups, which are kept for `rollup_secs`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Retention {
+    pub raw_secs: i64,
+    pub rollup_secs: i64,
+    pub rollup_bucket_secs: i64,
+    pub trim_interval_secs: u64,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            raw_secs: RAW_RETENTION_SECS,
+            rollup_secs: ROLLUP_RETENTION_SECS,
+            rollup_bucket_secs: 3600,
+            trim_interval_secs: 300,
+        }
+    }
+}
+
+/// Bounded queue between block fetching and persistence. Failed writes are
+/// retried up to `max_attempts` times, `backoff_ms` apart and doubling, then
+/// recorded as dead letters. Queued transactions of consecutive blocks of a
+/// chain are written together, up to `max_batch_txns` rows.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WriteQueueConfig {
+    pub capacity: usize,
+    pub workers: usize,
+    pub max_attempts: u32,
+    pub backoff_ms: u64,
+    pub max_batch_txns: usize,
+}
+
+impl Default for WriteQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            workers: 4,
+            max_attempts: 5,
+            backoff_ms: 500,
+            max_batch_txns: 10_000,
+        }
+    }
+}
+
+/// Memory held by the metric series of a chain.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CacheUsage {
+    pub chain_id: u64,
+    pub blocks: u64,
+    pub rollups: u64,
+    pub bytes: u64,
+}
+
+/// Registry entry of a chain, from `[[chains]]` in the config.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChainInfo {
+    pub chain_id: u64,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Symbol of the native token, `ETH` if unset.
+    #[serde(default)]
+    pub native_symbol: Option<String>,
+    /// Decimals of the native token, 18 if unset.
+    #[serde(default)]
+    pub decimals: Option<u8>,
+    #[serde(default)]
+    pub explorer_url: Option<String>,
+    /// Labels of the chain's endpoints, keyed by URL. Endpoints that are down
+    /// at startup are only attributed to the chain when listed here.
+    #[serde(default)]
+    pub endpoints: BTreeMap<String, String>,
+}
+
+impl ChainInfo {
+    /// Entry of a chain missing from the registry.
+    pub fn unlisted(chain_id: u64) -> Self {
+        Self {
+            chain_id,
+            name: None,
+            native_symbol: None,
+            decimals: None,
+            explorer_url: None,
+            endpoints: BTreeMap::new(),
+        }
+    }
+}
+
+/// How far the indexing of a chain is behind its node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestionState {
+    /// No block indexed yet.
+    Pending,
+    /// Within `LIVE_LAG_BLOCKS` of the node head.
+    Live,
+    CatchingUp,
+    /// No endpoint of the chain answers, so the lag is unknown.
+    Unreachable,
+}
+
+impl IngestionState {
+    pub fn new(indexed_height: Option<u64>, head_height: Option<u64>) -> Self {
+        match (indexed_height, head_height) {
+            (_, None) => IngestionState::Unreachable,
+            (None, Some(_)) => IngestionState::Pending,
+            (Some(indexed), Some(head)) if head.saturating_sub(indexed) <= LIVE_LAG_BLOCKS => {
+                IngestionState::Live
+            }
+            (Some(_), Some(_)) => IngestionState::CatchingUp,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    pub label: String,
+    pub up: bool,
+    /// Latest block number reported by the endpoint.
+    pub head_height: Option<u64>,
+    /// Why the endpoint is down: not connected, timed out or the error of its
+    /// answer.
+    pub error: Option<String>,
+}
+
+/// Registry entry of a chain together with the progress of its indexing.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChainStatus {
+    pub chain_id: u64,
+    pub name: Option<String>,
+    pub native_symbol: String,
+    pub decimals: u8,
+    pub explorer_url: Option<String>,
+    pub indexed_height: Option<u64>,
+    pub head_height: Option<u64>,
+    pub lag_blocks: Option<u64>,
+    /// Seconds between the timestamps of the head and the last indexed block.
+    pub lag_secs: Option<i64>,
+    /// Unix time in milliseconds of the last indexed block.
+    pub last_block_timestamp: Option<i64>,
+    pub last_block_time: Option<String>,
+    pub ingestion: IngestionState,
+    pub endpoints: Vec<EndpointStatus>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BridgedValueResponse {
+    pub received: String,
+    pub sent: String,
+    pub timestamp: i64,
+    pub time: String,
 }
 
 impl std::str::FromStr for Metric {
@@ -39,6 +412,10 @@ impl std::str::FromStr for Metric {
             "transaction_volume" => Ok(Metric::TransactionVolume),
             "total_transfers" => Ok(Metric::TotalTransactions),
             "successful_transfers" => Ok(Metric::SuccessfulTransfers),
+            "delivery_latency" => Ok(Metric::DeliveryLatency),
+            "bridged_value" => Ok(Metric::BridgedValue),
+            "failure_rate" => Ok(Metric::FailureRate),
+            "cache_usage" => Ok(Metric::CacheUsage),
             _ => Ok(Metric::CurrentTPS),
         }
     }
@@ -99,12 +476,187 @@ extract! {
 }
 
 extract! {
-    #[derive(Deserialize, Serialize)]
+    #[derive(Clone, Deserialize, Serialize)]
     pub struct ChainId {
         pub chain_id: Option<u64>
     }
 }
 
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SourceChainId {
+    pub source_chain_id: Option<u64>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Bucket {
+    pub bucket: Option<String>,
+}
+
+impl Bucket {
+    /// Bucket width in seconds, if one was requested.
+    pub fn duration(&self) -> Result<Option<i64>, String> {
+        match &self.bucket {
+            Some(bucket) => parse_duration(bucket)
+                .map(Some)
+                .ok_or_else(|| format!("invalid bucket: {}", bucket)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Parses durations such as `10`, `10s`, `5m`, `1h` or `1d` into seconds.
+pub fn parse_duration(input: &str) -> Option<i64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (value, unit) = input.split_at(split);
+    let value: i64 = value.parse().ok()?;
+    let multiplier = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+
+    value.checked_mul(multiplier).filter(|secs| *secs > 0)
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TimeZone {
+    pub tz: Option<String>,
+}
+
+impl TimeZone {
+    /// Display offset requested with `tz`, or `default` when it is absent.
+    pub fn offset(&self, default: FixedOffset) -> Result<FixedOffset, String> {
+        match &self.tz {
+            Some(tz) => parse_offset(tz).ok_or_else(|| format!("invalid tz: {}", tz)),
+            None => Ok(default),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TimeRange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl TimeRange {
+    /// `(from, to)` in unix seconds. `to` defaults to `latest_timestamp` and
+    /// `from` to `default_span` seconds before it.
+    pub fn bounds(&self, default_span: i64, latest_timestamp: i64) -> Result<(i64, i64), String> {
+        let to = match &self.to {
+            Some(to) => parse_timestamp(to).ok_or_else(|| format!("invalid to: {}", to))?,
+            None => latest_timestamp,
+        };
+        let from = match &self.from {
+            Some(from) => parse_timestamp(from).ok_or_else(|| format!("invalid from: {}", from))?,
+            None => to.saturating_sub(default_span),
+        };
+
+        if from >= to {
+            return Err("from must be before to".to_string());
+        }
+
+        Ok((from, to))
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct BlockId {
+    pub chain_id: u64,
+    pub height: u64,
+}
+
+/// Parses a unix timestamp (seconds or milliseconds) or an RFC 3339 date
+/// into unix seconds. Timestamps out of the millisecond range are rejected.
+pub fn parse_timestamp(input: &str) -> Option<i64> {
+    let input = input.trim();
+    if let Ok(timestamp) = input.parse::<i64>() {
+        return checked_unix_ms(timestamp).map(|timestamp_ms| timestamp_ms / 1000);
+    }
+
+    DateTime::parse_from_rfc3339(input)
+        .ok()
+        .map(|datetime| datetime.timestamp())
+}
+
+/// A validated metrics time range, in unix seconds, split into buckets of
+/// `bucket` seconds. Ranges are half-open: `(from, to]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub from: i64,
+    pub to: i64,
+    pub bucket: i64,
+}
+
+impl TimeWindow {
+    /// Resolves the query parameters against the metric's default span and
+    /// bucket. `to` defaults to `latest_timestamp`, and an explicit `from`
+    /// must lie within `retention_secs` of now.
+    pub fn resolve(
+        range: &TimeRange,
+        bucket: &Bucket,
+        (default_span, default_bucket): (i64, i64),
+        latest_timestamp: i64,
+        now: i64,
+        retention_secs: i64,
+    ) -> Result<Self, String> {
+        let (from, to) = range.bounds(default_span, latest_timestamp)?;
+        let bucket = bucket.duration()?.unwrap_or(default_bucket);
+
+        if bucket > retention_secs {
+            return Err(format!(
+                "bucket is longer than the metrics retention of {} seconds",
+                retention_secs
+            ));
+        }
+
+        if range.from.is_some() && from < now.saturating_sub(retention_secs) {
+            return Err(format!(
+                "from is older than the metrics retention of {} seconds",
+                retention_secs
+            ));
+        }
+
+        let window = Self { from, to, bucket };
+        if window.bucket_count() > MAX_BUCKETS {
+            return Err(format!(
+                "range spans more than {} buckets of {} seconds",
+                MAX_BUCKETS, bucket
+            ));
+        }
+
+        Ok(window)
+    }
+
+    pub fn span(&self) -> i64 {
+        self.to - self.from
+    }
+
+    pub fn bucket_count(&self) -> i64 {
+        (self.span() + self.bucket - 1) / self.bucket
+    }
+
+    /// Bucket boundaries `(start, end]`, oldest first.
+    pub fn buckets(&self) -> Vec<(i64, i64)> {
+        (0..self.bucket_count())
+            .map(|idx| {
+                let start = self.from + idx * self.bucket;
+                (start, (start + self.bucket).min(self.to))
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Window {
+    pub window: Option<u64>,
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Stride {
     pub stride: Option<u64>,
@@ -115,13 +667,50 @@ pub struct Type {
     pub tx_type: Option<Tx>,
 }
 
-#[derive(Clone, Debug, Serialize, Eq, PartialEq)]
+#[derive(Clone, Debug, Serialize, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Tx {
     Native,
+    ContractCall,
+    Deployment,
+    TokenTransfer,
     CrossChain,
+    System,
     All,
 }
 
+impl Tx {
+    /// Every concrete category, i.e. everything except `All`.
+    pub const CATEGORIES: [Tx; 6] = [
+        Tx::Native,
+        Tx::ContractCall,
+        Tx::Deployment,
+        Tx::TokenTransfer,
+        Tx::CrossChain,
+        Tx::System,
+    ];
+
+    /// Block hash field holding the number of transfers, failed or not.
+    /// For `All` this is the number of transactions in the block.
+    pub fn total_field(&self) -> String {
+        format!("total:{}", self)
+    }
+
+    /// Block hash field holding the number of successful transfers.
+    pub fn successful_field(&self) -> String {
+        format!("successful:{}", self)
+    }
+
+    /// Key holding the count of this category in the latest block.
+    pub fn tps_key(&self, chain_id: &u64) -> String {
+        match self {
+            Tx::Native => format!("chain:{{{}}}:ntps", chain_id),
+            Tx::CrossChain => format!("chain:{{{}}}:xtps", chain_id),
+            Tx::All => format!("chain:{{{}}}:tps", chain_id),
+            other => format!("chain:{{{}}}:tps_{}", chain_id, other),
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for Tx {
     fn deserialize<D>(deserializer: D) -> Result<Tx, D::Error>
     where
@@ -133,19 +722,16 @@ impl<'de> Deserialize<'de> for Tx {
             type Value = Tx;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str(r#""native" or "cross_chain" or "all""#)
+                formatter.write_str(
+                    r#""native", "contract_call", "deployment", "token_transfer", "cross_chain", "system" or "all""#,
+                )
             }
 
             fn visit_str<E>(self, v: &str) -> Result<Tx, E>
             where
                 E: serde::de::Error,
             {
-                match v.to_lowercase().as_str() {
-                    "native" => Ok(Tx::Native),
-                    "cross_chain" | "crosschain" => Ok(Tx::CrossChain),
-                    "all" => Ok(Tx::All),
-                    _ => Err(E::custom(format!("invalid tx_type: {}", v))),
-                }
+                parse_tx(v).ok_or_else(|| E::custom(format!("invalid tx_type: {}", v)))
             }
         }
 
@@ -153,16 +739,26 @@ impl<'de> Deserialize<'de> for Tx {
     }
 }
 
+/// `crosschain` and `bridge_delivery` are accepted as aliases of `cross_chain`.
+fn parse_tx(input: &str) -> Option<Tx> {
+    match input.to_lowercase().as_str() {
+        "native" => Some(Tx::Native),
+        "contract_call" => Some(Tx::ContractCall),
+        "deployment" => Some(Tx::Deployment),
+        "token_transfer" => Some(Tx::TokenTransfer),
+        "cross_chain" | "crosschain" | "bridge_delivery" => Some(Tx::CrossChain),
+        "system" => Some(Tx::System),
+        "all" => Some(Tx::All),
+        _ => None,
+    }
+}
+
 impl std::str::FromStr for Tx {
     type Err = serde_json::Error;
 
     fn from_str(input: &str) -> Result<Tx, Self::Err> {
-        match input {
-            "native" => Ok(Tx::Native),
-            "cross_chain" => Ok(Tx::CrossChain),
-            "all" => Ok(Tx::All),
-            _ => Ok(Tx::Native),
-        }
+        parse_tx(input)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid tx_type: {}", input)))
     }
 }
 
@@ -170,7 +766,11 @@ impl fmt::Display for Tx {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
             Tx::Native => "native",
+            Tx::ContractCall => "contract_call",
+            Tx::Deployment => "deployment",
+            Tx::TokenTransfer => "token_transfer",
             Tx::CrossChain => "cross_chain",
+            Tx::System => "system",
             Tx::All => "all",
         };
         write!(f, "{}", s)
@@ -221,28 +821,29 @@ pub struct TxnSummary {
     pub block_height: u64,
 }
 
-impl From<Transaction> for TxnSummary {
-    fn from(tx: Transaction) -> Self {
-        TxnSummary {
+impl TryFrom<Transaction> for TxnSummary {
+    type Error = ConversionError;
+
+    /// Fails for pending transactions, which have no block yet.
+    fn try_from(tx: Transaction) -> Result<Self, Self::Error> {
+        Ok(TxnSummary {
             hash: tx.hash.to_hex_string(),
-            block_hash: Some(tx.block_hash.unwrap().to_hex_string()),
-            to: if tx.to.is_none() {
-                None
-            } else {
-                Some(tx.to.unwrap().to_hex_string())
-            },
+            block_hash: tx.block_hash.map(|hash| hash.to_hex_string()),
+            to: tx.to.map(|to| to.to_hex_string()),
             from: tx.from.to_hex_string(),
             status: Some(1),
             value: tx.value.to_hex_string(),
-            block_height: tx.block_number.unwrap(),
-        }
+            block_height: tx
+                .block_number
+                .ok_or(ConversionError::MissingField("block_number"))?,
+        })
     }
 }
 
 #[derive(Deserialize, Serialize)]
 pub enum TxAPIResponse {
     TxnSummary(TxnSummary),
-    Transaction(Transaction),
+    Transaction(Box<Transaction>),
 }
 
 sol! {
@@ -276,7 +877,7 @@ pub fn parse_logs(receipt: &TransactionReceipt) -> (bool, u32) {
         }
     }
 
-    return (false, 0);
+    (false, 0)
 }
 
 pub trait ConvertToHex {
@@ -365,23 +966,140 @@ fn internal(bytes: &[u8]) -> String {
     bytes.encode_hex_with_prefix()
 }
 
-#[warn(dead_code)]
-pub fn unix_ms_to_ist(timestamp: i64) -> String {
-    let timestamp_ms = if timestamp < 1_000_000_000_000 {
-        timestamp * 1000
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("SystemTime before UNIX EPOCH!")
+        .as_secs() as i64
+}
+
+/// Normalizes a unix timestamp given in either seconds or milliseconds to
+/// milliseconds, saturating out of range.
+pub fn to_unix_ms(timestamp: i64) -> i64 {
+    checked_unix_ms(timestamp).unwrap_or(if timestamp < 0 { i64::MIN } else { i64::MAX })
+}
+
+/// Normalizes a unix timestamp given in either seconds or milliseconds to
+/// milliseconds, or `None` if it does not fit.
+pub fn checked_unix_ms(timestamp: i64) -> Option<i64> {
+    if timestamp < 1_000_000_000_000 {
+        timestamp.checked_mul(1000)
     } else {
-        timestamp
-    };
+        Some(timestamp)
+    }
+}
 
-    let secs = timestamp_ms / 1000;
-    let nanos = (timestamp_ms % 1000) * 1_000_000;
-    let naive_dt =
-        NaiveDateTime::from_timestamp_opt(secs, nanos as u32).expect("Invalid timestamp");
-    let utc_dt = DateTime::<Utc>::from_naive_utc_and_offset(naive_dt, Utc);
-    let ist_offset = FixedOffset::east_opt(5 * 3600 + 30 * 60).expect("Invalid offset");
-    let ist_dt = utc_dt.with_timezone(&ist_offset);
+/// Formats a unix timestamp (seconds or milliseconds) as an RFC 3339 string
+/// in `offset`, e.g. `2025-03-16T15:53:59.000Z`. Fails for timestamps out of
+/// the supported date range.
+pub fn format_timestamp(timestamp: i64, offset: &FixedOffset) -> Result<String, String> {
+    checked_unix_ms(timestamp)
+        .and_then(DateTime::<Utc>::from_timestamp_millis)
+        .map(|datetime| {
+            datetime
+                .with_timezone(offset)
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+        })
+        .ok_or_else(|| format!("invalid timestamp: {}", timestamp))
+}
+
+/// Parses `UTC` or a fixed offset such as `+05:30`, `-0800` or `+09`.
+/// A missing sign reads as `+`, since query strings decode `+` as a space.
+pub fn parse_offset(input: &str) -> Option<FixedOffset> {
+    let input = input.trim();
+    let input = input
+        .strip_prefix("UTC")
+        .or_else(|| input.strip_prefix("utc"))
+        .unwrap_or(input)
+        .trim();
+    if input.is_empty() || input.eq_ignore_ascii_case("z") {
+        return FixedOffset::east_opt(0);
+    }
 
-    ist_dt.format("%Y-%m-%d %H:%M:%S%.3f IST").to_string()
+    let (sign, input) = match input.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, input.strip_prefix('+').unwrap_or(input)),
+    };
+    let digits: String = input.chars().filter(|c| *c != ':').collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes): (i32, i32) = match digits.len() {
+        1 | 2 => (digits.parse().ok()?, 0),
+        4 => (digits[..2].parse().ok()?, digits[2..].parse().ok()?),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
 }
 
 pub const MAX_WINDOW_SIZE: u64 = 25;
+pub const DEFAULT_TPS_WINDOW: u64 = 20;
+pub const MAX_TPS_WINDOW: u64 = 1000;
+pub const MAX_BUCKETS: i64 = 3600;
+pub const RAW_RETENTION_SECS: i64 = 7 * 86400;
+pub const ROLLUP_RETENTION_SECS: i64 = 90 * 86400;
+pub const LIVE_LAG_BLOCKS: u64 = 5;
+pub const DEFAULT_NATIVE_SYMBOL: &str = "ETH";
+pub const DEFAULT_DECIMALS: u8 = 18;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1742140439;
+
+    fn resolve(from: Option<String>, bucket: Option<&str>) -> Result<TimeWindow, String> {
+        let range = TimeRange { from, to: None };
+        let bucket = Bucket {
+            bucket: bucket.map(str::to_string),
+        };
+        TimeWindow::resolve(&range, &bucket, (3600, 60), NOW, NOW, RAW_RETENTION_SECS)
+    }
+
+    #[test]
+    fn parse_timestamp_rejects_out_of_range_seconds() {
+        assert_eq!(parse_timestamp("1742140439"), Some(1742140439));
+        assert_eq!(parse_timestamp("1742140439000"), Some(1742140439));
+        assert_eq!(parse_timestamp(&i64::MIN.to_string()), None);
+        assert_eq!(parse_timestamp("-9300000000000000"), None);
+    }
+
+    #[test]
+    fn bounds_reject_overflowing_range() {
+        let range = TimeRange {
+            from: Some(i64::MIN.to_string()),
+            to: None,
+        };
+        assert!(range.bounds(3600, 1742140439).is_err());
+    }
+
+    #[test]
+    fn resolve_rejects_overflowing_range() {
+        assert!(resolve(Some(i64::MIN.to_string()), None).is_err());
+    }
+
+    #[test]
+    fn resolve_rejects_buckets_longer_than_the_retention() {
+        assert_eq!(
+            resolve(None, Some("1h")).map(|window| window.bucket_count()),
+            Ok(1)
+        );
+        assert!(resolve(None, Some("106751991167300d")).is_err());
+        assert!(resolve(None, Some(&format!("{}", RAW_RETENTION_SECS + 1))).is_err());
+    }
+
+    #[test]
+    fn format_timestamp_rejects_out_of_range_dates() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        assert_eq!(
+            format_timestamp(1742140439, &utc).as_deref(),
+            Ok("2025-03-16T15:53:59.000Z")
+        );
+        assert!(format_timestamp(i64::MAX, &utc).is_err());
+        assert!(format_timestamp(i64::MIN, &utc).is_err());
+    }
+}
@@ -1,9 +1,15 @@
-use alloy::{eips::BlockNumberOrTag, providers::Provider, rpc::types::Block};
+use alloy::{
+    eips::BlockNumberOrTag, primitives::U256, providers::Provider, rpc::types::Block,
+    transports::RpcError,
+};
 use async_std::task::sleep;
-use db::{parse_logs, provider::InternalDataProvider, ConvertToHex, Tx};
+use db::{
+    parse_bridge_events, provider::InternalDataProvider, BlockModel, BridgeEvents, Classifier,
+    ConvertToHex, SourceEvent, Tx, TxCounts,
+};
 use futures::stream::{FuturesUnordered, StreamExt};
 use std::{collections::BTreeMap, sync::Arc, time};
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::error::IndexerError;
 use crate::indexer::ExternalProvider;
@@ -13,7 +19,14 @@ pub(crate) async fn catch_up_blocks(
     internal_provider: Arc<InternalDataProvider>,
     external_provider: ExternalProvider,
     chain_id: &u64,
+    source_events: Arc<Vec<SourceEvent>>,
+    classifier: Arc<Classifier>,
+    max_block_attempts: u32,
 ) -> Result<(), IndexerError> {
+    // recorded failures of the block being indexed, and consecutive failures
+    // of any kind, which set the backoff
+    let mut failed_attempts = 0;
+    let mut retries = 0;
     let (mut indexer_block_height, mut query_param) = if let Some(ht) = indexer_start_height {
         (ht, BlockNumberOrTag::Number(ht + 1))
     } else {
@@ -44,52 +57,84 @@ pub(crate) async fn catch_up_blocks(
             if indexer_block_height == 0 || indexer_block_height != validator_max_height {
                 indexer_block_height = current_block.header.number;
 
-                let (total_xfers, failed_xfers, total_native_transfers, total_x_chain_transfers) =
-                    match process_block(
-                        &current_block,
-                        chain_id,
-                        &external_provider,
-                        internal_provider.clone(),
-                    )
-                    .await
-                    {
-                        Ok((
-                            total_xfers,
-                            failed_xfers,
-                            total_native_transfers,
-                            total_x_chain_transfers,
-                        )) => (
-                            total_xfers,
-                            failed_xfers,
-                            total_native_transfers,
-                            total_x_chain_transfers,
-                        ),
-                        Err(_) => {
+                let category_counts = match index_block(
+                    &current_block,
+                    chain_id,
+                    &external_provider,
+                    internal_provider.clone(),
+                    &source_events,
+                    &classifier,
+                )
+                .await
+                {
+                    Ok(category_counts) => category_counts,
+                    Err(e) => {
+                        let height = current_block.header.number;
+                        retries += 1;
+                        if e.is_block_error() {
+                            match internal_provider
+                                .record_block_failure(*chain_id, height, e.to_string())
+                                .await
+                            {
+                                Ok(attempts) => failed_attempts = attempts,
+                                Err(e) => tracing::error!("{}", e.to_string()),
+                            }
+                        }
+                        if !e.is_block_error()
+                            || max_block_attempts == 0
+                            || failed_attempts < max_block_attempts
+                        {
+                            let backoff =
+                                SLEEP.saturating_mul(1 << retries.min(MAX_BACKOFF_DOUBLINGS));
+                            warn!(
+                                "Failed to index block {} of chain {}, retrying in {}ms: {}",
+                                height, chain_id, backoff, e
+                            );
+                            sleep(time::Duration::from_millis(backoff)).await;
                             break;
                         }
-                    };
 
-                info!(
-                    "current height {} validator height {}, total_xfers {}, failed_xfers {}, native_txns {}. x_chain_xfers {}, chain id {}",
-                    current_block.header.number, validator_max_height, total_xfers, failed_xfers, total_native_transfers, total_x_chain_transfers, chain_id,
-                );
+                        warn!(
+                            "Skipping block {} of chain {} after {} failed attempts: {}",
+                            height, chain_id, failed_attempts, e
+                        );
+                        if let Err(e) = internal_provider.skip_failed_block(*chain_id, height).await
+                        {
+                            tracing::error!("{}", e.to_string());
+                        }
+                        failed_attempts = 0;
+                        retries = 0;
+                        query_param = BlockNumberOrTag::Number(height.saturating_add(1));
+                        continue;
+                    }
+                };
 
-                if let Ok(()) = internal_provider
-                    .add_block(
-                        chain_id,
-                        current_block.header.timestamp as i64,
-                        total_xfers.saturating_sub(failed_xfers),
-                        total_xfers,
-                        total_native_transfers,
-                        total_x_chain_transfers,
-                        current_block.transactions.len(),
-                        current_block.header.number,
-                    )
+                retries = 0;
+                // the block may have failed before a restart, which resets
+                // `failed_attempts`
+                failed_attempts = 0;
+                if let Err(e) = internal_provider
+                    .remove_failed_block(*chain_id, current_block.header.number)
                     .await
                 {
-                } else {
-                    break;
+                    tracing::error!("{}", e.to_string());
                 }
+
+                let total_xfers: u64 = category_counts.values().map(|counts| counts.total).sum();
+                let failed_xfers: u64 = category_counts.values().map(|counts| counts.failed).sum();
+                let native_txns = category_counts
+                    .get(&Tx::Native)
+                    .copied()
+                    .unwrap_or_default();
+                let x_chain_xfers = category_counts
+                    .get(&Tx::CrossChain)
+                    .copied()
+                    .unwrap_or_default();
+
+                info!(
+                    "current height {} validator height {}, total_xfers {}, failed_xfers {}, native_txns {}. x_chain_xfers {}, chain id {}",
+                    current_block.header.number, validator_max_height, total_xfers, failed_xfers, native_txns.total, x_chain_xfers.total, chain_id,
+                );
             }
 
             query_param = BlockNumberOrTag::Number(indexer_block_height.saturating_add(1));
@@ -98,76 +143,135 @@ pub(crate) async fn catch_up_blocks(
     }
 }
 
+/// Processes a block and records its metrics, returning its counts per
+/// category.
+pub(crate) async fn index_block(
+    block: &Block,
+    chain_id: &u64,
+    external_provider: &ExternalProvider,
+    internal_provider: Arc<InternalDataProvider>,
+    source_events: &[SourceEvent],
+    classifier: &Classifier,
+) -> Result<BTreeMap<Tx, TxCounts>, IndexerError> {
+    let (category_counts, received_by_source) = process_block(
+        block,
+        chain_id,
+        external_provider,
+        internal_provider.clone(),
+        source_events,
+        classifier,
+    )
+    .await?;
+
+    let timestamp = block.header.timestamp as i64;
+    let tx_count = block.transactions.len();
+    internal_provider
+        .add_block(
+            chain_id,
+            timestamp,
+            &category_counts,
+            &received_by_source,
+            tx_count,
+            block.header.number,
+        )
+        .await?;
+
+    Ok(category_counts)
+}
+
 pub async fn process_block(
     block: &Block,
     chain_id: &u64,
     external_provider: &ExternalProvider,
     internal_provider: Arc<InternalDataProvider>,
-) -> Result<(u64, u64, u64, u64), IndexerError> {
-    let mut total = 0;
-    let mut failed = 0;
-    let mut total_native_transfers = 0;
-    let mut total_x_chain_transfers = 0;
+    source_events: &[SourceEvent],
+    classifier: &Classifier,
+) -> Result<(BTreeMap<Tx, TxCounts>, BTreeMap<u64, U256>), IndexerError> {
+    let mut category_counts: BTreeMap<Tx, TxCounts> = BTreeMap::new();
     let transactions: Vec<_> = block.transactions.txns().cloned().collect();
     let mut tx_map = BTreeMap::new();
+    let mut bridge_events = BridgeEvents::default();
     let tasks: FuturesUnordered<_> = transactions
         .iter()
         .map(|tx| async move {
-            let is_transfer =
-                (tx.input.is_empty() || tx.input.to_hex_string() == "0x") && tx.to.is_some();
             let tx_hash = tx.hash.to_hex_string();
-            if !is_transfer {
-                if let Some(receipt) = external_provider
-                    .get_transaction_receipt(tx.hash)
-                    .await
-                    .ok()
-                    .flatten()
-                {
-                    let is_failed = !receipt.status();
-                    let (_, xtps) = parse_logs(&receipt);
-                    Some((
-                        xtps as u64,
-                        is_failed as u64,
-                        xtps as u64,
-                        tx_hash,
-                        Tx::CrossChain,
-                    ))
-                } else {
-                    None
+            let value = tx.value;
+            // even plain value transfers can fail, or use more than the
+            // intrinsic gas when the recipient is a contract
+            let receipt = match external_provider.get_transaction_receipt(tx.hash).await {
+                Ok(Some(receipt)) => receipt,
+                Ok(None) => {
+                    return Err(IndexerError::BlockError(format!(
+                        "missing receipt of {}",
+                        tx_hash
+                    )))
                 }
-            } else {
-                Some((1, 0, 0, tx_hash, Tx::Native))
-            }
+                Err(RpcError::Transport(e)) => {
+                    return Err(IndexerError::ProviderError(e.to_string()))
+                }
+                Err(e) => {
+                    return Err(IndexerError::BlockError(format!(
+                        "receipt of {}: {}",
+                        tx_hash, e
+                    )))
+                }
+            };
+            let is_failed = !receipt.status();
+            let gas_used = receipt.gas_used as u64;
+            let events = parse_bridge_events(*chain_id, &receipt, source_events);
+            let tx_type = classifier.classify(tx, Some(&receipt));
+            Ok((is_failed, gas_used, value, tx_hash, tx_type, events))
         })
         .collect();
 
     let results = tasks.collect::<Vec<_>>().await;
+    // every category counts transactions, so that they add up to `Tx::All`
     for result in results {
-        if let Some((t, f, cross_chain_xfers, tx_hash, tx_type)) = result {
-            total += t;
-            failed += f;
-            total_native_transfers += if tx_type == Tx::Native { 1 } else { 0 };
-            total_x_chain_transfers += cross_chain_xfers;
-            tx_map.insert(tx_hash, tx_type);
+        let (is_failed, gas_used, value, tx_hash, tx_type, events) = result?;
+        let counts = category_counts.entry(tx_type.clone()).or_default();
+        counts.total += 1;
+        if is_failed {
+            counts.failed += 1;
         }
+        counts.gas_used += gas_used;
+        counts.value = counts.value.saturating_add(value);
+        tx_map.insert(tx_hash, tx_type);
+        bridge_events.extend(events);
     }
 
-    let chain_id = *chain_id;
-    tokio::spawn(async move {
-        if let Err(e) = internal_provider
-            .add_txns(chain_id, transactions.len(), transactions, tx_map)
-            .await
-        {
-            tracing::error!("{}", e.to_string());
-        }
-    });
-
-    Ok((
-        total,
-        failed,
-        total_native_transfers,
-        total_x_chain_transfers,
-    ))
+    // the bridged value is written along with the block's counts
+    let received_by_source = bridge_events.received_by_source();
+
+    let block_record = BlockModel::new(
+        *chain_id,
+        block.header.number,
+        block.header.hash.to_hex_string(),
+        block.header.timestamp as i64,
+        transactions.len(),
+        &category_counts,
+        &received_by_source,
+    );
+    // queued writes wait for room in the write queue, which holds back the
+    // fetch loop while the database is behind
+    internal_provider.add_block_record(block_record).await?;
+
+    if !bridge_events.is_empty() {
+        internal_provider
+            .add_bridge_events(
+                bridge_events,
+                block.header.number,
+                block.header.timestamp as i64,
+            )
+            .await?;
+    }
+
+    internal_provider
+        .add_txns(*chain_id, transactions.len(), transactions, tx_map)
+        .await?;
+
+    Ok((category_counts, received_by_source))
 }
 
 const SLEEP: u64 = 500;
+/// Caps the backoff between attempts at a block to about 8.5 minutes.
+const MAX_BACKOFF_DOUBLINGS: u32 = 10;
@@ -1,4 +1,5 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use db::{ChainInfo, MetricsBackend, Retention, SourceEvent, StorageBackend, WriteQueueConfig};
 use serde::{Deserialize, Serialize};
 use std::fs;
 
@@ -6,6 +7,24 @@ use std::fs;
 pub struct CLIArguments {
     #[clap(long, value_parser)]
     pub config_path: Option<String>,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Rewrite the metrics of blocks persisted in Postgres, then exit.
+    RebuildCache {
+        #[clap(long, value_parser)]
+        chain_id: Option<u64>,
+        /// Unix timestamp or RFC 3339 date, defaults to the raw retention
+        /// period before `to`.
+        #[clap(long, value_parser)]
+        from: Option<String>,
+        /// Unix timestamp or RFC 3339 date, defaults to now.
+        #[clap(long, value_parser)]
+        to: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -13,6 +32,35 @@ pub struct IndexerConfig {
     pub listening_port: u16,
     pub geth_endpoints: Vec<String>,
     pub indexer_start_heights: Vec<i64>,
+    #[serde(default)]
+    pub source_events: Vec<SourceEvent>,
+    /// Names, native tokens, explorers and endpoint labels of the chains,
+    /// reported by `/chains`.
+    #[serde(default)]
+    pub chains: Vec<ChainInfo>,
+    /// Offset used to display timestamps when a request has no `tz`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub retention: Retention,
+    /// Database persisting indexed data, `postgres` or `sqlite`.
+    #[serde(default)]
+    pub storage: StorageBackend,
+    /// Where the metric series live, `redis` or `memory`.
+    #[serde(default)]
+    pub metrics_store: MetricsBackend,
+    #[serde(default)]
+    pub write_queue: WriteQueueConfig,
+    /// Failed attempts after which a block is skipped, left for a retry
+    /// through `/admin/failed_blocks/retry`. With 0, failing blocks are
+    /// retried forever. Only errors of the block itself count, not outages of
+    /// the node or the databases.
+    #[serde(default = "default_max_block_attempts")]
+    pub max_block_attempts: u32,
+    /// Bearer token required by the `/admin` routes, which are disabled
+    /// without one.
+    #[serde(default)]
+    pub admin_token: Option<String>,
 }
 
 impl Default for IndexerConfig {
@@ -21,10 +69,23 @@ impl Default for IndexerConfig {
             listening_port: 9090,
             geth_endpoints: vec!["http://139.59.46.36:22001".to_string()],
             indexer_start_heights: vec![438200],
+            source_events: Vec::new(),
+            chains: Vec::new(),
+            timezone: None,
+            retention: Retention::default(),
+            storage: StorageBackend::default(),
+            metrics_store: MetricsBackend::default(),
+            write_queue: WriteQueueConfig::default(),
+            max_block_attempts: default_max_block_attempts(),
+            admin_token: None,
         }
     }
 }
 
+fn default_max_block_attempts() -> u32 {
+    10
+}
+
 pub(crate) fn load_config(config_path: &str) -> std::result::Result<IndexerConfig, String> {
     match fs::read_to_string(config_path) {
         Ok(file_str) => {
@@ -15,6 +15,7 @@ pub struct ResponseError {
 }
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum IndexerError {
     #[error("Deserialization Error")]
     DeserializationError(#[from] SerdeError),
@@ -28,8 +29,32 @@ pub enum IndexerError {
     #[error("Redis Error: {0}")]
     RedisError(#[from] RedisError),
 
+    #[error("Storage Error: {0}")]
+    StorageError(#[from] std::io::Error),
+
     #[error("External Provider Error")]
     ProviderError(String),
+
+    /// The node answered, but the data of a block could not be indexed.
+    #[error("Block Error: {0}")]
+    BlockError(String),
+
+    #[error("Invalid Parameter: {0}")]
+    InvalidParameter(String),
+
+    #[error("Unauthorized")]
+    Unauthorized,
+}
+
+impl IndexerError {
+    /// Whether the error comes from the block being indexed rather than from
+    /// an outage of the node or the databases, which retries can outlast.
+    pub fn is_block_error(&self) -> bool {
+        matches!(
+            self,
+            IndexerError::BlockError(_) | IndexerError::DeserializationError(_)
+        )
+    }
 }
 
 impl warp::reject::Reject for IndexerError {}
@@ -44,9 +69,13 @@ pub(crate) async fn handle_rejection(
         Some(IndexerError::ReqwestError(_)) => (StatusCode::BAD_REQUEST, "Reqwest Error"),
         Some(IndexerError::TokioJoinError(_)) => (StatusCode::BAD_REQUEST, "Tokio Join Error"),
         Some(IndexerError::RedisError(_)) => (StatusCode::BAD_REQUEST, "Redis Error"),
+        Some(IndexerError::StorageError(_)) => (StatusCode::INTERNAL_SERVER_ERROR, "Storage Error"),
         Some(IndexerError::ProviderError(_)) => {
             (StatusCode::BAD_REQUEST, "External Provider Error")
         }
+        Some(IndexerError::BlockError(_)) => (StatusCode::INTERNAL_SERVER_ERROR, "Block Error"),
+        Some(IndexerError::InvalidParameter(_)) => (StatusCode::BAD_REQUEST, "Invalid Parameter"),
+        Some(IndexerError::Unauthorized) => (StatusCode::UNAUTHORIZED, "Unauthorized"),
         None => (StatusCode::BAD_REQUEST, "Unknown Error Code"),
     };
 
@@ -2,10 +2,10 @@ use alloy::{
     providers::{Provider, RootProvider},
     transports::http::Http,
 };
-use db::provider::InternalDataProvider;
+use db::{provider::InternalDataProvider, ChainId, Classifier, SourceEvent};
 use reqwest::Client;
 use std::{collections::BTreeMap, sync::Arc, time::Duration};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock};
 
 use crate::{catchup::catch_up_blocks, config::IndexerConfig};
 
@@ -14,7 +14,11 @@ pub struct Indexer {
     pub internal_provider: Arc<InternalDataProvider>,
     pub external_providers: BTreeMap<u64, ExternalProvider>,
     pub inactive_providers: BTreeMap<String, ExternalProvider>,
+    pub chain_endpoints: ChainEndpoints,
     pub indexer_start_heights: BTreeMap<u64, Option<u64>>,
+    pub source_events: Arc<Vec<SourceEvent>>,
+    pub classifier: Arc<Classifier>,
+    pub max_block_attempts: u32,
 }
 
 impl Indexer {
@@ -23,6 +27,7 @@ impl Indexer {
         internal_provider: Arc<InternalDataProvider>,
         external_providers: BTreeMap<u64, ExternalProvider>,
         inactive_providers: BTreeMap<String, ExternalProvider>,
+        chain_endpoints: ChainEndpoints,
     ) -> Self {
         let mut chain_ids = Vec::new();
         let mut indexer_heights = BTreeMap::new();
@@ -32,7 +37,7 @@ impl Indexer {
             let indexer_ht = if config.indexer_start_heights[idx] == -1 {
                 None
             } else {
-                Some(config.indexer_start_heights[idx].clone() as u64)
+                Some(config.indexer_start_heights[idx] as u64)
             };
             chain_ids.push(chain_id);
             indexer_heights.insert(chain_id, indexer_ht);
@@ -44,6 +49,10 @@ impl Indexer {
             chain_ids,
             indexer_start_heights: indexer_heights,
             inactive_providers,
+            chain_endpoints,
+            source_events: Arc::new(config.source_events),
+            classifier: Arc::new(Classifier::default()),
+            max_block_attempts: config.max_block_attempts,
         }
     }
 
@@ -57,12 +66,18 @@ impl Indexer {
                     .get(chain_id)
                     .expect("Irrecoverable Error: Start height should be present.");
                 let chain_id = *chain_id;
+                let source_events = self.source_events.clone();
+                let classifier = self.classifier.clone();
+                let max_block_attempts = self.max_block_attempts;
                 tokio::spawn(async move {
                     let _ = catch_up_blocks(
                         indexer_start_height,
                         internal_provider,
                         external_provider,
                         &chain_id,
+                        source_events,
+                        classifier,
+                        max_block_attempts,
                     )
                     .await;
                 });
@@ -70,11 +85,65 @@ impl Indexer {
         }
 
         self.poll_inactive_providers().await;
+        self.match_bridge_messages().await;
+        self.trim_metrics().await;
+    }
+
+    pub async fn trim_metrics(&self) {
+        let internal_provider = self.internal_provider.clone();
+        let interval = Duration::from_secs(internal_provider.retention.trim_interval_secs.max(1));
+
+        tokio::spawn(async move {
+            loop {
+                match internal_provider.trim_metrics().await {
+                    Ok(0) => {}
+                    Ok(trimmed) => tracing::info!("Rolled up {} blocks", trimmed),
+                    Err(e) => tracing::error!("Failed to trim metrics: {}", e),
+                }
+
+                if let Ok(usage) = internal_provider
+                    .cache_usage(ChainId { chain_id: None })
+                    .await
+                {
+                    for chain in usage {
+                        tracing::info!(
+                            "chain id {} cache usage {} bytes, {} blocks, {} rollups",
+                            chain.chain_id,
+                            chain.bytes,
+                            chain.blocks,
+                            chain.rollups
+                        );
+                    }
+                }
+
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    pub async fn match_bridge_messages(&self) {
+        let internal_provider = self.internal_provider.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match internal_provider.match_bridge_messages().await {
+                    Ok(0) => {}
+                    Ok(matched) => tracing::info!("Matched {} bridge messages", matched),
+                    Err(e) => tracing::error!("Failed to match bridge messages: {}", e),
+                }
+
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            }
+        });
     }
 
     pub async fn poll_inactive_providers(&self) {
         let inactive_providers = Arc::new(Mutex::new(self.inactive_providers.clone()));
+        let chain_endpoints = self.chain_endpoints.clone();
         let internal_provider = self.internal_provider.clone();
+        let source_events = self.source_events.clone();
+        let classifier = self.classifier.clone();
+        let max_block_attempts = self.max_block_attempts;
 
         tokio::spawn(async move {
             loop {
@@ -93,13 +162,27 @@ impl Indexer {
                         };
 
                         to_remove.push(endpoint.clone());
+                        chain_endpoints
+                            .write()
+                            .await
+                            .insert(chain_id, endpoint.clone());
 
                         let provider = provider.clone();
                         let internal_provider = internal_provider.clone();
+                        let source_events = source_events.clone();
+                        let classifier = classifier.clone();
                         tracing::info!("Inactive provider {}, is back live. Catching up", endpoint);
                         tokio::spawn(async move {
-                            let _ =
-                                catch_up_blocks(None, internal_provider, provider, &chain_id).await;
+                            let _ = catch_up_blocks(
+                                None,
+                                internal_provider,
+                                provider,
+                                &chain_id,
+                                source_events,
+                                classifier,
+                                max_block_attempts,
+                            )
+                            .await;
                         });
                     }
                 }
@@ -116,3 +199,7 @@ impl Indexer {
 }
 
 pub type ExternalProvider = RootProvider<Http<Client>>;
+
+/// URL of the endpoint each live chain is indexed from. Chains whose endpoint
+/// comes back up are added while the server runs.
+pub type ChainEndpoints = Arc<RwLock<BTreeMap<u64, String>>>;
@@ -1,13 +1,14 @@
 use clap::Parser;
+use db::{provider::InternalDataProvider, unix_now, TimeRange};
 use dotenvy::dotenv;
-use tracing::Level;
+use tracing::{info, Level};
 
 mod config;
 mod error;
 mod routes;
 mod server;
 
-use crate::config::{load_config, CLIArguments};
+use crate::config::{load_config, CLIArguments, Command};
 use crate::server::Server;
 
 mod catchup;
@@ -23,6 +24,22 @@ async fn main() -> Result<(), std::io::Error> {
     let config_path = cli_args.config_path.unwrap_or(String::new());
     let config = load_config(&config_path).expect("Irrecoverable error: fail to load config.toml");
 
+    if let Some(Command::RebuildCache { chain_id, from, to }) = cli_args.command {
+        let provider = InternalDataProvider::new(
+            config.retention.clone(),
+            config.storage,
+            config.metrics_store,
+            config.write_queue.clone(),
+        )
+        .await?;
+        let (from, to) = TimeRange { from, to }
+            .bounds(config.retention.raw_secs, unix_now())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let restored = provider.rebuild_cache(chain_id, from, to).await?;
+        info!("restored {} blocks from {} to {}", restored, from, to);
+        return Ok(());
+    }
+
     Server::new(config).await?.start().await?;
 
     Ok(())
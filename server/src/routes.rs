@@ -1,9 +1,20 @@
-use alloy::{primitives::TxHash, providers::Provider};
-use db::{provider::InternalDataProvider, types::*};
-use std::{collections::BTreeMap, convert::Infallible, str::FromStr, sync::Arc};
+use alloy::{eips::BlockNumberOrTag, primitives::TxHash, providers::Provider};
+use db::{provider::InternalDataProvider, types::*, Classifier, FixedOffset, SourceEvent};
+use futures::future::join_all;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 use warp::{self, http, Filter};
 
-use crate::{error::IndexerError, indexer::ExternalProvider};
+use crate::{
+    catchup::index_block,
+    error::IndexerError,
+    indexer::{ChainEndpoints, ExternalProvider},
+};
 
 pub(crate) fn index_route(
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -73,13 +84,17 @@ pub(crate) fn transactions(
                         )))
                     })?;
 
-                return Ok(warp::reply::json(&vec![TxAPIResponse::Transaction(tx)]));
+                return Ok(warp::reply::json(&vec![TxAPIResponse::Transaction(
+                    Box::new(tx),
+                )]));
             }
 
             for (_chain_id, provider) in external_provider_map {
                 match provider.get_transaction_by_hash(hash).await {
                     Ok(Some(tx)) => {
-                        return Ok(warp::reply::json(&vec![TxAPIResponse::Transaction(tx)]));
+                        return Ok(warp::reply::json(&vec![TxAPIResponse::Transaction(
+                            Box::new(tx),
+                        )]));
                     }
                     Ok(None) => continue,
                     Err(_e) => {
@@ -133,13 +148,21 @@ pub(crate) fn transactions(
 
 pub(crate) fn metrics(
     provider: Arc<InternalDataProvider>,
+    timezone: FixedOffset,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_metrics(
         metric: String,
         provider: Arc<InternalDataProvider>,
         identifier: ChainId,
         stride: Stride,
         tx_type: Type,
+        source: SourceChainId,
+        tps_window: Window,
+        range: TimeRange,
+        bucket: Bucket,
+        tz: TimeZone,
+        timezone: FixedOffset,
     ) -> Result<impl warp::Reply, warp::Rejection> {
         let performance_metric: Metric = match Metric::from_str(&metric) {
             Ok(f) => f,
@@ -148,40 +171,87 @@ pub(crate) fn metrics(
             }
         };
 
+        let latest_timestamp = provider.latest_timestamp(&identifier).await;
+        let window = TimeWindow::resolve(
+            &range,
+            &bucket,
+            performance_metric.default_window(&stride),
+            latest_timestamp,
+            unix_now(),
+            provider.retention.rollup_secs,
+        )
+        .map_err(|e| warp::reject::custom(IndexerError::InvalidParameter(e)))?;
+        let offset = tz
+            .offset(timezone)
+            .map_err(|e| warp::reject::custom(IndexerError::InvalidParameter(e)))?;
+        // buckets lie between the bounds, so they format once the bounds do
+        for bound in [window.from, window.to] {
+            format_timestamp(bound, &offset)
+                .map_err(|e| warp::reject::custom(IndexerError::InvalidParameter(e)))?;
+        }
+
         match performance_metric {
             Metric::CurrentTPS => {
-                let tps = provider.current_tps(identifier, tx_type).await.unwrap_or(0);
+                let tps = provider
+                    .current_tps(identifier, tx_type, tps_window)
+                    .await
+                    .unwrap_or_default();
 
                 Ok(warp::reply::json(&tps))
             }
             Metric::TransactionVolume => {
                 let tx_volume = provider
-                    .transaction_volume(identifier, tx_type, stride)
+                    .transaction_volume(identifier, tx_type, window, offset)
                     .await
                     .unwrap_or(Vec::new());
                 Ok(warp::reply::json(&tx_volume))
             }
             Metric::TotalTransactions => {
                 let total_txns = provider
-                    .total_xfers_last_day(identifier, tx_type)
+                    .total_xfers(identifier, tx_type, window)
                     .await
                     .unwrap_or(0);
                 Ok(warp::reply::json(&total_txns))
             }
             Metric::SuccessfulTransfers => {
                 let successful_xfers = provider
-                    .successful_xfers_last_day(identifier, tx_type)
+                    .successful_xfers(identifier, tx_type, window)
                     .await
                     .unwrap_or(0);
                 Ok(warp::reply::json(&successful_xfers))
             }
             Metric::LiveTPS => {
                 let tps = provider
-                    .live_tps(identifier, stride, tx_type)
+                    .live_tps(identifier, tx_type, window, offset)
                     .await
                     .unwrap_or(Vec::new());
                 Ok(warp::reply::json(&tps))
             }
+            Metric::DeliveryLatency => {
+                let latencies = provider
+                    .delivery_latency(identifier, source, window)
+                    .await
+                    .unwrap_or(Vec::new());
+                Ok(warp::reply::json(&latencies))
+            }
+            Metric::FailureRate => {
+                let failure_rate = provider
+                    .failure_rate(identifier, tx_type, window)
+                    .await
+                    .unwrap_or_default();
+                Ok(warp::reply::json(&failure_rate))
+            }
+            Metric::BridgedValue => {
+                let bridged_value = provider
+                    .bridged_value(identifier, window, offset)
+                    .await
+                    .unwrap_or(Vec::new());
+                Ok(warp::reply::json(&bridged_value))
+            }
+            Metric::CacheUsage => {
+                let usage = provider.cache_usage(identifier).await.unwrap_or(Vec::new());
+                Ok(warp::reply::json(&usage))
+            }
         }
     }
 
@@ -191,11 +261,424 @@ pub(crate) fn metrics(
             .and(warp::query::<ChainId>())
             .and(warp::query::<Stride>())
             .and(warp::query::<Type>())
+            .and(warp::query::<SourceChainId>())
+            .and(warp::query::<Window>())
+            .and(warp::query::<TimeRange>())
+            .and(warp::query::<Bucket>())
+            .and(warp::query::<TimeZone>())
             .and(warp::path::end())
-            .and_then(move |metric, identifier, stride, tx_type| {
-                get_metrics(metric, Arc::clone(&provider), identifier, stride, tx_type)
-            })
+            .and_then(
+                move |metric,
+                      identifier,
+                      stride,
+                      tx_type,
+                      source,
+                      tps_window,
+                      range,
+                      bucket,
+                      tz| {
+                    get_metrics(
+                        metric,
+                        Arc::clone(&provider),
+                        identifier,
+                        stride,
+                        tx_type,
+                        source,
+                        tps_window,
+                        range,
+                        bucket,
+                        tz,
+                        timezone,
+                    )
+                },
+            )
     };
 
     get_metrics_route(provider.clone())
 }
+
+pub(crate) fn chains(
+    provider: Arc<InternalDataProvider>,
+    registry: Arc<BTreeMap<u64, ChainInfo>>,
+    endpoints: Arc<BTreeMap<String, ExternalProvider>>,
+    chain_endpoints: ChainEndpoints,
+    timezone: FixedOffset,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    /// Status of the registered, live and indexed chains.
+    pub async fn get_chains(
+        provider: Arc<InternalDataProvider>,
+        registry: Arc<BTreeMap<u64, ChainInfo>>,
+        endpoints: Arc<BTreeMap<String, ExternalProvider>>,
+        chain_endpoints: ChainEndpoints,
+        timezone: FixedOffset,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        // the endpoints are queried without holding the lock
+        let chain_endpoints = chain_endpoints.read().await.clone();
+        let mut chain_ids: BTreeSet<u64> = registry.keys().copied().collect();
+        chain_ids.extend(chain_endpoints.keys().copied());
+        chain_ids.extend(provider.get_chains().await.unwrap_or_default());
+
+        let statuses = join_all(chain_ids.into_iter().map(|chain_id| {
+            chain_status(
+                chain_id,
+                &provider,
+                &registry,
+                &endpoints,
+                &chain_endpoints,
+                &timezone,
+            )
+        }))
+        .await;
+
+        Ok(warp::reply::json(&statuses))
+    }
+
+    pub async fn get_chain(
+        chain_id: u64,
+        provider: Arc<InternalDataProvider>,
+        registry: Arc<BTreeMap<u64, ChainInfo>>,
+        endpoints: Arc<BTreeMap<String, ExternalProvider>>,
+        chain_endpoints: ChainEndpoints,
+        timezone: FixedOffset,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let chain_endpoints = chain_endpoints.read().await.clone();
+        let known = registry.contains_key(&chain_id)
+            || chain_endpoints.contains_key(&chain_id)
+            || provider.indexed_head(&chain_id).await.is_some();
+        if !known {
+            return Err(warp::reject::custom(IndexerError::InvalidParameter(
+                format!("Unknown chain ID: {}", chain_id),
+            )));
+        }
+
+        let status = chain_status(
+            chain_id,
+            &provider,
+            &registry,
+            &endpoints,
+            &chain_endpoints,
+            &timezone,
+        )
+        .await;
+
+        Ok(warp::reply::json(&status))
+    }
+
+    let get_chains_route = |provider: Arc<InternalDataProvider>,
+                            registry: Arc<BTreeMap<u64, ChainInfo>>,
+                            endpoints: Arc<BTreeMap<String, ExternalProvider>>,
+                            chain_endpoints: ChainEndpoints| {
+        warp::path!("chains")
+            .and(warp::get())
+            .and(warp::path::end())
+            .and_then(move || {
+                get_chains(
+                    Arc::clone(&provider),
+                    Arc::clone(&registry),
+                    Arc::clone(&endpoints),
+                    Arc::clone(&chain_endpoints),
+                    timezone,
+                )
+            })
+    };
+
+    let get_chain_route = |provider: Arc<InternalDataProvider>,
+                           registry: Arc<BTreeMap<u64, ChainInfo>>,
+                           endpoints: Arc<BTreeMap<String, ExternalProvider>>,
+                           chain_endpoints: ChainEndpoints| {
+        warp::path!("chains" / u64)
+            .and(warp::get())
+            .and(warp::path::end())
+            .and_then(move |chain_id| {
+                get_chain(
+                    chain_id,
+                    Arc::clone(&provider),
+                    Arc::clone(&registry),
+                    Arc::clone(&endpoints),
+                    Arc::clone(&chain_endpoints),
+                    timezone,
+                )
+            })
+    };
+
+    get_chains_route(
+        provider.clone(),
+        registry.clone(),
+        endpoints.clone(),
+        chain_endpoints.clone(),
+    )
+    .or(get_chain_route(
+        provider.clone(),
+        registry.clone(),
+        endpoints.clone(),
+        chain_endpoints.clone(),
+    ))
+}
+
+/// Queries the endpoints of a chain for their head block, and compares the
+/// highest one with the latest indexed block.
+async fn chain_status(
+    chain_id: u64,
+    provider: &InternalDataProvider,
+    registry: &BTreeMap<u64, ChainInfo>,
+    endpoints: &BTreeMap<String, ExternalProvider>,
+    chain_endpoints: &BTreeMap<u64, String>,
+    timezone: &FixedOffset,
+) -> ChainStatus {
+    let info = registry
+        .get(&chain_id)
+        .cloned()
+        .unwrap_or_else(|| ChainInfo::unlisted(chain_id));
+
+    let mut urls: BTreeSet<&String> = info.endpoints.keys().collect();
+    urls.extend(chain_endpoints.get(&chain_id));
+
+    // endpoints are asked at once, and a slow one is reported rather than
+    // holding back the response
+    let head_blocks = join_all(urls.iter().map(|url| async move {
+        let endpoint = endpoints.get(*url).ok_or("not connected".to_string())?;
+        match tokio::time::timeout(
+            ENDPOINT_TIMEOUT,
+            endpoint.get_block_by_number(BlockNumberOrTag::Latest, false),
+        )
+        .await
+        {
+            Ok(Ok(Some(block))) => Ok((block.header.number, block.header.timestamp)),
+            Ok(Ok(None)) => Err("no head block".to_string()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("timed out after {}s", ENDPOINT_TIMEOUT.as_secs())),
+        }
+    }))
+    .await;
+
+    // number and timestamp of the highest head block
+    let head: Option<(u64, u64)> = head_blocks
+        .iter()
+        .flatten()
+        .max_by_key(|(number, _)| *number)
+        .copied();
+    let endpoint_statuses = urls
+        .into_iter()
+        .zip(head_blocks)
+        .map(|(url, head_block)| EndpointStatus {
+            label: info.endpoints.get(url).unwrap_or(url).clone(),
+            up: head_block.is_ok(),
+            head_height: head_block.as_ref().ok().map(|(number, _)| *number),
+            error: head_block.err(),
+        })
+        .collect();
+
+    let indexed = provider.indexed_head(&chain_id).await;
+    let indexed_height = indexed.map(|(height, _)| height);
+    let head_height = head.map(|(number, _)| number);
+
+    ChainStatus {
+        chain_id,
+        name: info.name,
+        native_symbol: info
+            .native_symbol
+            .unwrap_or_else(|| DEFAULT_NATIVE_SYMBOL.to_string()),
+        decimals: info.decimals.unwrap_or(DEFAULT_DECIMALS),
+        explorer_url: info.explorer_url,
+        indexed_height,
+        head_height,
+        lag_blocks: indexed_height
+            .zip(head_height)
+            .map(|(indexed, head)| head.saturating_sub(indexed)),
+        lag_secs: indexed
+            .zip(head)
+            .map(|((_, timestamp), (_, head_timestamp))| {
+                (head_timestamp as i64 - timestamp / 1000).max(0)
+            }),
+        last_block_timestamp: indexed.map(|(_, timestamp)| timestamp),
+        last_block_time: indexed
+            .and_then(|(_, timestamp)| format_timestamp(timestamp, timezone).ok()),
+        ingestion: IngestionState::new(indexed_height, head_height),
+        endpoints: endpoint_statuses,
+    }
+}
+
+pub(crate) fn admin(
+    provider: Arc<InternalDataProvider>,
+    external_provider_map: BTreeMap<u64, ExternalProvider>,
+    source_events: Arc<Vec<SourceEvent>>,
+    classifier: Arc<Classifier>,
+    admin_token: Option<String>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    pub async fn rebuild_cache(
+        provider: Arc<InternalDataProvider>,
+        identifier: ChainId,
+        range: TimeRange,
+        authorization: Option<String>,
+        admin_token: Option<String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        authorize(authorization, admin_token)?;
+
+        let (from, to) = range
+            .bounds(provider.retention.raw_secs, unix_now())
+            .map_err(|e| warp::reject::custom(IndexerError::InvalidParameter(e)))?;
+
+        let restored = provider
+            .rebuild_cache(identifier.chain_id, from, to)
+            .await
+            .map_err(|e| warp::reject::custom(IndexerError::ProviderError(e.to_string())))?;
+
+        Ok(warp::reply::json(&restored))
+    }
+
+    pub async fn get_failed_blocks(
+        provider: Arc<InternalDataProvider>,
+        identifier: ChainId,
+        authorization: Option<String>,
+        admin_token: Option<String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        authorize(authorization, admin_token)?;
+
+        let failed_blocks = provider
+            .get_failed_blocks(identifier.chain_id)
+            .await
+            .map_err(|e| warp::reject::custom(IndexerError::ProviderError(e.to_string())))?;
+
+        Ok(warp::reply::json(&failed_blocks))
+    }
+
+    /// Indexes a failed block again, responding with its number of transfers.
+    pub async fn retry_failed_block(
+        provider: Arc<InternalDataProvider>,
+        external_provider_map: BTreeMap<u64, ExternalProvider>,
+        source_events: Arc<Vec<SourceEvent>>,
+        classifier: Arc<Classifier>,
+        block_id: BlockId,
+        authorization: Option<String>,
+        admin_token: Option<String>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        authorize(authorization, admin_token)?;
+
+        let external_provider = external_provider_map
+            .get(&block_id.chain_id)
+            .ok_or_else(|| {
+                warp::reject::custom(IndexerError::InvalidParameter(format!(
+                    "No provider found for chain ID: {}",
+                    block_id.chain_id
+                )))
+            })?;
+        let block = external_provider
+            .get_block_by_number(BlockNumberOrTag::Number(block_id.height), true)
+            .await
+            .map_err(|e| warp::reject::custom(IndexerError::ProviderError(e.to_string())))?
+            .ok_or_else(|| {
+                warp::reject::custom(IndexerError::InvalidParameter(format!(
+                    "Block {} not found on chain {}",
+                    block_id.height, block_id.chain_id
+                )))
+            })?;
+
+        match index_block(
+            &block,
+            &block_id.chain_id,
+            external_provider,
+            provider.clone(),
+            &source_events,
+            &classifier,
+        )
+        .await
+        {
+            Ok(category_counts) => {
+                provider
+                    .remove_failed_block(block_id.chain_id, block_id.height)
+                    .await
+                    .map_err(|e| {
+                        warp::reject::custom(IndexerError::ProviderError(e.to_string()))
+                    })?;
+
+                let transfers: u64 = category_counts.values().map(|counts| counts.total).sum();
+                Ok(warp::reply::json(&transfers))
+            }
+            Err(e) => {
+                let _ = provider
+                    .record_block_failure(block_id.chain_id, block_id.height, e.to_string())
+                    .await;
+                Err(warp::reject::custom(e))
+            }
+        }
+    }
+
+    let rebuild_cache_route = |provider: Arc<InternalDataProvider>, admin_token: Option<String>| {
+        warp::path!("admin" / "rebuild_cache")
+            .and(warp::post())
+            .and(warp::query::<ChainId>())
+            .and(warp::query::<TimeRange>())
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::path::end())
+            .and_then(move |identifier, range, authorization| {
+                rebuild_cache(
+                    Arc::clone(&provider),
+                    identifier,
+                    range,
+                    authorization,
+                    admin_token.clone(),
+                )
+            })
+    };
+
+    let failed_blocks_route = |provider: Arc<InternalDataProvider>, admin_token: Option<String>| {
+        warp::path!("admin" / "failed_blocks")
+            .and(warp::get())
+            .and(warp::query::<ChainId>())
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::path::end())
+            .and_then(move |identifier, authorization| {
+                get_failed_blocks(
+                    Arc::clone(&provider),
+                    identifier,
+                    authorization,
+                    admin_token.clone(),
+                )
+            })
+    };
+
+    let retry_failed_block_route =
+        |provider: Arc<InternalDataProvider>, admin_token: Option<String>| {
+            warp::path!("admin" / "failed_blocks" / "retry")
+                .and(warp::post())
+                .and(warp::query::<BlockId>())
+                .and(warp::header::optional::<String>("authorization"))
+                .and(warp::path::end())
+                .and_then(move |block_id, authorization| {
+                    retry_failed_block(
+                        Arc::clone(&provider),
+                        external_provider_map.clone(),
+                        source_events.clone(),
+                        classifier.clone(),
+                        block_id,
+                        authorization,
+                        admin_token.clone(),
+                    )
+                })
+        };
+
+    rebuild_cache_route(provider.clone(), admin_token.clone())
+        .or(failed_blocks_route(provider.clone(), admin_token.clone()))
+        .or(retry_failed_block_route(
+            provider.clone(),
+            admin_token.clone(),
+        ))
+}
+
+/// Admin routes are disabled unless an `admin_token` is configured.
+fn authorize(
+    authorization: Option<String>,
+    admin_token: Option<String>,
+) -> Result<(), warp::Rejection> {
+    match (authorization, admin_token) {
+        (Some(authorization), Some(admin_token))
+            if authorization.strip_prefix("Bearer ") == Some(admin_token.as_str()) =>
+        {
+            Ok(())
+        }
+        _ => Err(warp::reject::custom(IndexerError::Unauthorized)),
+    }
+}
+
+/// Time an endpoint has to report its head block to `/chains`.
+const ENDPOINT_TIMEOUT: Duration = Duration::from_secs(2);
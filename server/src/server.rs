@@ -1,13 +1,14 @@
 use alloy::providers::{Provider, ProviderBuilder};
-use db::provider::InternalDataProvider;
+use db::{parse_offset, provider::InternalDataProvider, ChainInfo, FixedOffset};
 use std::{collections::BTreeMap, sync::Arc};
+use tokio::sync::RwLock;
 use tracing::info;
 use warp::Filter;
 
 use crate::{
     config::IndexerConfig,
     error::handle_rejection,
-    indexer::{ExternalProvider, Indexer},
+    indexer::{ChainEndpoints, ExternalProvider, Indexer},
     routes::*,
 };
 
@@ -16,15 +17,30 @@ pub(crate) struct Server {
     external_providers: BTreeMap<u64, ExternalProvider>,
     internal_data_provider: Arc<InternalDataProvider>,
     inactive_providers: BTreeMap<String, ExternalProvider>,
+    /// Every configured endpoint, live or not, keyed by URL.
+    endpoints: Arc<BTreeMap<String, ExternalProvider>>,
+    chain_endpoints: ChainEndpoints,
+    timezone: FixedOffset,
 }
 
 impl Server {
     pub async fn new(config: IndexerConfig) -> Result<Server, std::io::Error> {
+        let timezone =
+            parse_offset(config.timezone.as_deref().unwrap_or("UTC")).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("invalid timezone: {:?}", config.timezone),
+                )
+            })?;
+
         let mut external_providers = BTreeMap::new();
         let mut inactive_providers = BTreeMap::new();
+        let mut endpoints = BTreeMap::new();
+        let mut chain_endpoints = BTreeMap::new();
 
-        for (_idx, endpoint) in config.geth_endpoints.clone().iter().enumerate() {
+        for endpoint in config.geth_endpoints.clone().iter() {
             let provider = ProviderBuilder::new().on_http(endpoint.parse().unwrap());
+            endpoints.insert(endpoint.to_string(), provider.clone());
             let chain_id = match provider.get_chain_id().await {
                 Ok(id) => id,
                 Err(_) => {
@@ -34,35 +50,70 @@ impl Server {
                 }
             };
             external_providers.insert(chain_id, provider);
+            chain_endpoints.insert(chain_id, endpoint.to_string());
         }
 
+        let internal_data_provider = Arc::new(
+            InternalDataProvider::new(
+                config.retention.clone(),
+                config.storage,
+                config.metrics_store,
+                config.write_queue.clone(),
+            )
+            .await?,
+        );
+
         Ok(Server {
             config,
             external_providers,
-            internal_data_provider: Arc::new(InternalDataProvider::new().await?),
+            internal_data_provider,
             inactive_providers,
+            endpoints: Arc::new(endpoints),
+            chain_endpoints: Arc::new(RwLock::new(chain_endpoints)),
+            timezone,
         })
     }
 
     pub async fn start(self) -> Result<(), std::io::Error> {
-        let listening_port = self.config.listening_port.clone();
-        let _ = Indexer::new(
+        let listening_port = self.config.listening_port;
+        let admin_token = self.config.admin_token.clone();
+        let registry: BTreeMap<u64, ChainInfo> = self
+            .config
+            .chains
+            .iter()
+            .map(|chain| (chain.chain_id, chain.clone()))
+            .collect();
+        let indexer = Indexer::new(
             self.config,
             self.internal_data_provider.clone(),
             self.external_providers.clone(),
             self.inactive_providers,
+            self.chain_endpoints.clone(),
         )
-        .await
-        .bootstrap()
         .await;
+        indexer.bootstrap().await;
 
         let warp_serve = warp::serve(
             index_route()
-                .or(metrics(self.internal_data_provider.clone()))
+                .or(metrics(self.internal_data_provider.clone(), self.timezone))
                 .or(transactions(
                     self.internal_data_provider.clone(),
                     self.external_providers.clone(),
                 ))
+                .or(chains(
+                    self.internal_data_provider.clone(),
+                    Arc::new(registry),
+                    self.endpoints,
+                    self.chain_endpoints,
+                    self.timezone,
+                ))
+                .or(admin(
+                    self.internal_data_provider.clone(),
+                    self.external_providers.clone(),
+                    indexer.source_events.clone(),
+                    indexer.classifier.clone(),
+                    admin_token,
+                ))
                 .recover(handle_rejection)
                 .with(warp::cors().allow_any_origin()),
         );